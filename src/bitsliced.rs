@@ -0,0 +1,225 @@
+use super::{
+    get_index, hash_kernel, parameters, BitVec, BloomError, BloomFilter, BloomHash, SeededState,
+};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// an index over many BloomFilters of the same shape, answering "which
+// filters may contain this item?" without probing them one by one.
+//
+// the filters' bitmaps are stored transposed: row i holds bit i of every
+// filter, one bit per slot. a query ANDs the k rows its item hashes to,
+// leaving the bitmap of the filters with all k bits set. this is the
+// bit-sliced signature layout of BitFunnel and COBS.
+//
+// an index created with `items_count`, `fp_rate` and hashers holds the
+// filters created by `BloomFilter` with the same parameters and hashers;
+// `new` picks random seeds, so filters must be created with the index's
+// `seeds` to be added to it.
+pub struct BitSlicedBloomIndex<T: ?Sized, S = SeededState> {
+    rows: Vec<BitVec>,
+    // the slots holding a filter; removed filters leave free slots, which
+    // are reused before the rows grow.
+    occupied: BitVec,
+    free: Vec<usize>,
+    optimal_m: usize,
+    optimal_k: u32,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(BitSlicedBloomIndex(items_count: usize, fp_rate: f64));
+
+impl<T: ?Sized, S: BuildHasher> BitSlicedBloomIndex<T, S> {
+    // create a new, empty BitSlicedBloomIndex like `new`, for filters whose
+    // two hash functions are derived from `hasher1` and `hasher2`, returning
+    // an error instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        let (optimal_m, optimal_k) = parameters(items_count, fp_rate)?;
+
+        Ok(BitSlicedBloomIndex {
+            rows: vec![BitVec::new(); optimal_m],
+            occupied: BitVec::new(),
+            free: Vec::new(),
+            optimal_m,
+            optimal_k,
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of filters in the index.
+    pub fn len(&self) -> usize {
+        self.occupied.len() - self.free.len()
+    }
+
+    // check if there are no filters in the index.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // get the number of slots, occupied or free; the bitmaps returned by
+    // `contains` have one bit per slot.
+    pub fn slots(&self) -> usize {
+        self.occupied.len()
+    }
+
+    // check if `slot` holds a filter.
+    pub fn is_occupied(&self, slot: usize) -> bool {
+        self.occupied.get(slot).unwrap_or(false)
+    }
+
+    // add a copy of `filter` to the index, returning the slot it was given.
+    // returns an error if the filter was not created with the index's
+    // parameters and hashers.
+    pub fn add_filter(&mut self, filter: &BloomFilter<T, S>) -> Result<usize, BloomError>
+    where
+        S: PartialEq,
+    {
+        if filter.optimal_m != self.optimal_m {
+            return Err(BloomError::MismatchedOptimalM(
+                self.optimal_m,
+                filter.optimal_m,
+            ));
+        }
+        if filter.optimal_k != self.optimal_k {
+            return Err(BloomError::MismatchedOptimalK(
+                self.optimal_k,
+                filter.optimal_k,
+            ));
+        }
+        if filter.hashers != self.hashers {
+            return Err(BloomError::MismatchedHashers);
+        }
+
+        // a free slot's column was cleared by `remove_filter`.
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                for row in &mut self.rows {
+                    row.push(false);
+                }
+                self.occupied.push(false);
+                self.occupied.len() - 1
+            }
+        };
+
+        for (row, bit) in self.rows.iter_mut().zip(filter.bitmap.iter()) {
+            if bit {
+                row.set(slot, true);
+            }
+        }
+        self.occupied.set(slot, true);
+
+        Ok(slot)
+    }
+
+    // remove the filter in `slot` from the index, freeing the slot for the
+    // next filter added. returns false if the slot held no filter.
+    pub fn remove_filter(&mut self, slot: usize) -> bool {
+        if !self.is_occupied(slot) {
+            return false;
+        }
+
+        for row in &mut self.rows {
+            row.set(slot, false);
+        }
+        self.occupied.set(slot, false);
+        self.free.push(slot);
+
+        true
+    }
+
+    // get the bitmap of the slots whose filter may contain an item.
+    // false positives are possible, but not false negatives.
+    pub fn contains<Q>(&self, item: &Q) -> BitVec
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+        self.contains_bloom_hash(BloomHash { h1, h2 })
+    }
+
+    // get the bitmap of the slots whose filter may contain the item whose
+    // hash is `hash`.
+    pub fn contains_bloom_hash(&self, hash: BloomHash) -> BitVec {
+        let mut candidates = self.occupied.clone();
+        for k_i in 0..self.optimal_k {
+            let index = get_index(hash.h1, hash.h2, k_i as u64, self.optimal_m);
+            candidates.and(&self.rows[index]);
+        }
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(keys: std::ops::Range<u64>) -> BloomFilter<u64> {
+        let mut bloom = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        for key in keys {
+            bloom.insert(&key);
+        }
+        bloom
+    }
+
+    #[test]
+    fn matches_probing_each_filter() {
+        let mut index = BitSlicedBloomIndex::with_seeds(100, 0.01, 1, 2);
+        let filters: Vec<_> = (0..300).map(|i| filter(i * 100..(i + 1) * 100)).collect();
+        for (i, bloom) in filters.iter().enumerate() {
+            assert_eq!(index.add_filter(bloom), Ok(i));
+        }
+
+        for key in (0..40_000).step_by(7) {
+            let candidates = index.contains(&key);
+            let expected = BitVec::from_fn(300, |i| filters[i].contains(&key));
+            assert_eq!(candidates, expected);
+        }
+        assert!(index.contains(&12_345)[123]);
+    }
+
+    #[test]
+    fn remove_and_reuse_slots() {
+        let mut index = BitSlicedBloomIndex::with_seeds(100, 0.01, 1, 2);
+        for i in 0..4 {
+            index.add_filter(&filter(i * 100..(i + 1) * 100)).unwrap();
+        }
+
+        assert!(index.remove_filter(1));
+        assert!(!index.remove_filter(1));
+        assert!(!index.remove_filter(10));
+        assert_eq!(index.len(), 3);
+        assert!((100..200).all(|key| !index.contains(&key)[1]));
+
+        // the freed slot is reused, holding only the new filter's bits.
+        assert_eq!(index.add_filter(&filter(1000..1100)), Ok(1));
+        assert_eq!(index.slots(), 4);
+        assert!(index.contains(&1050)[1]);
+        assert!((100..200).filter(|key| index.contains(key)[1]).count() < 10);
+    }
+
+    #[test]
+    fn rejects_incompatible_filters() {
+        let mut index: BitSlicedBloomIndex<u64> = BitSlicedBloomIndex::with_seeds(100, 0.01, 1, 2);
+        assert!(matches!(
+            index.add_filter(&BloomFilter::with_seeds(200, 0.01, 1, 2)),
+            Err(BloomError::MismatchedOptimalM(_, _))
+        ));
+        assert_eq!(
+            index.add_filter(&BloomFilter::with_seeds(100, 0.01, 1, 3)),
+            Err(BloomError::MismatchedHashers)
+        );
+        assert!(index.is_empty());
+        assert!(index.contains(&1).is_empty());
+    }
+}
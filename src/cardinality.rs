@@ -0,0 +1,116 @@
+use super::{BloomError, BloomFilter};
+use std::hash::BuildHasher;
+
+impl<T: ?Sized, S: BuildHasher> BloomFilter<T, S> {
+    // estimate the number of distinct items in the set from the number of
+    // set bits (Swamidass & Baldi, 2007). returns infinity once every bit
+    // is set, since the filter then cannot tell how many items it holds.
+    pub fn estimated_len(&self) -> f64 {
+        self.estimate(self.bitmap.count_ones())
+    }
+
+    // estimate the number of distinct items in either set.
+    pub fn estimated_union_len(&self, other: &Self) -> Result<f64, BloomError>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        Ok(self.estimate(self.union_count_ones(other)))
+    }
+
+    // estimate the number of distinct items in both sets, as the sum of the
+    // estimated lengths minus the estimated length of the union.
+    pub fn estimated_intersection_len(&self, other: &Self) -> Result<f64, BloomError>
+    where
+        S: PartialEq,
+    {
+        let union = self.estimated_union_len(other)?;
+
+        // once the union is saturated the difference is meaningless.
+        if union.is_infinite() {
+            return Ok(f64::INFINITY);
+        }
+
+        Ok((self.estimated_len() + other.estimated_len() - union).max(0.0))
+    }
+
+    // estimate the number of distinct items that set `ones` bits:
+    // n = -(m / k) * ln(1 - ones / m).
+    fn estimate(&self, ones: u64) -> f64 {
+        let m = self.optimal_m as f64;
+        let k = self.optimal_k as f64;
+
+        -(m / k) * (1.0 - ones as f64 / m).ln()
+    }
+
+    // count the bits set in either bitmap without building the union.
+    fn union_count_ones(&self, other: &Self) -> u64 {
+        let blocks = self.bitmap.storage().iter().zip(other.bitmap.storage());
+        blocks.map(|(a, b)| (a | b).count_ones() as u64).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(items: std::ops::Range<i32>) -> BloomFilter<i32> {
+        let mut bloom = BloomFilter::with_seeds(10_000, 0.01, 1, 2);
+        for i in items {
+            bloom.insert(&i);
+        }
+        bloom
+    }
+
+    fn assert_close(estimate: f64, actual: f64) {
+        assert!(
+            (estimate - actual).abs() <= actual * 0.05 + 1.0,
+            "estimated {}, expected about {}",
+            estimate,
+            actual
+        );
+    }
+
+    #[test]
+    fn estimated_len() {
+        assert_eq!(filter(0..0).estimated_len(), 0.0);
+        assert_close(filter(0..1000).estimated_len(), 1000.0);
+        assert_close(filter(0..5000).estimated_len(), 5000.0);
+
+        let mut duplicates = filter(0..1000);
+        for i in 0..1000 {
+            duplicates.insert(&i);
+        }
+        assert_close(duplicates.estimated_len(), 1000.0);
+    }
+
+    #[test]
+    fn estimated_union_and_intersection_len() {
+        let a = filter(0..3000);
+        let b = filter(2000..5000);
+
+        assert_close(a.estimated_union_len(&b).unwrap(), 5000.0);
+        assert_close(a.estimated_intersection_len(&b).unwrap(), 1000.0);
+        assert_close(a.estimated_intersection_len(&filter(5000..6000)).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn saturated_filter() {
+        let mut bloom: BloomFilter<i32> = BloomFilter::with_seeds(10, 0.5, 1, 2);
+        for i in 0..1000 {
+            bloom.insert(&i);
+        }
+
+        assert_eq!(bloom.estimated_len(), f64::INFINITY);
+        assert_eq!(bloom.estimated_intersection_len(&bloom).unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn rejects_incompatible_filters() {
+        let other: BloomFilter<i32> = BloomFilter::with_seeds(10_000, 0.01, 3, 4);
+        assert_eq!(
+            filter(0..10).estimated_union_len(&other),
+            Err(BloomError::MismatchedHashers)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use super::{
+    bitmap_from_words, bitmap_words, get_index, hash_kernel, parameters, BloomError, BloomFilter,
+    SeededState,
+};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// a BloomFilter that can be inserted into and queried from many threads at
+// once. bits are stored in atomic words and set with `fetch_or`, so neither
+// `insert` nor `contains` takes a lock.
+//
+// created for `items_count` items at a false positive rate of `fp_rate`,
+// it is sized and hashes like `BloomFilter`: filters created with the same
+// parameters and hashers set the same bits for the same items, and convert
+// into each other with `From`.
+pub struct ConcurrentBloomFilter<T: ?Sized, S = SeededState> {
+    words: Box<[AtomicU64]>,
+    optimal_m: usize,
+    optimal_k: u32,
+    fp_rate: f64,
+    insertions: AtomicU64,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(ConcurrentBloomFilter(items_count: usize, fp_rate: f64));
+
+impl<T: ?Sized, S: BuildHasher> ConcurrentBloomFilter<T, S> {
+    // create a new ConcurrentBloomFilter like `new`, deriving its two hash
+    // functions from `hasher1` and `hasher2`, returning an error instead of
+    // panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        let (optimal_m, optimal_k) = parameters(items_count, fp_rate)?;
+
+        Ok(ConcurrentBloomFilter {
+            words: (0..optimal_m.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            optimal_m,
+            optimal_k,
+            fp_rate,
+            insertions: AtomicU64::new(0),
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the hash builders the two hash functions are derived from.
+    pub fn hashers(&self) -> (&S, &S) {
+        (&self.hashers[0], &self.hashers[1])
+    }
+
+    // get the number of times `insert` has been called, counting repeated
+    // insertions of the same item.
+    pub fn insertions(&self) -> u64 {
+        self.insertions.load(Ordering::Relaxed)
+    }
+
+    // insert items into the set.
+    pub fn insert(&self, item: &T)
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+
+        for k_i in 0..self.optimal_k {
+            let index = get_index(h1, h2, k_i as u64, self.optimal_m);
+            self.words[index / 64].fetch_or(1 << (index % 64), Ordering::Relaxed);
+        }
+
+        self.insertions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives: an item is
+    // reported present once an `insert` of it happens-before the query,
+    // e.g. when the inserting thread was joined or signalled the querying
+    // one. bits are accessed with relaxed ordering, so a query racing an
+    // insert may see only some of its bits.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+
+        (0..self.optimal_k).all(|k_i| {
+            let index = get_index(h1, h2, k_i as u64, self.optimal_m);
+            self.words[index / 64].load(Ordering::Relaxed) & 1 << (index % 64) != 0
+        })
+    }
+}
+
+impl<T: ?Sized, S> From<BloomFilter<T, S>> for ConcurrentBloomFilter<T, S> {
+    fn from(bloom: BloomFilter<T, S>) -> Self {
+        ConcurrentBloomFilter {
+            words: bitmap_words(&bloom.bitmap).map(AtomicU64::new).collect(),
+            optimal_m: bloom.optimal_m,
+            optimal_k: bloom.optimal_k,
+            fp_rate: bloom.fp_rate,
+            insertions: AtomicU64::new(bloom.insertions),
+            hashers: bloom.hashers,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, S> From<ConcurrentBloomFilter<T, S>> for BloomFilter<T, S> {
+    fn from(bloom: ConcurrentBloomFilter<T, S>) -> Self {
+        let words: Vec<u64> = bloom.words.iter().map(|word| word.load(Ordering::Relaxed)).collect();
+
+        BloomFilter {
+            bitmap: bitmap_from_words(&words, bloom.optimal_m),
+            optimal_m: bloom.optimal_m,
+            optimal_k: bloom.optimal_k,
+            fp_rate: bloom.fp_rate,
+            insertions: bloom.insertions.into_inner(),
+            hashers: bloom.hashers,
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn insert() {
+        let bloom = ConcurrentBloomFilter::new(100, 0.01);
+        assert!(!bloom.contains("item"));
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+        assert_eq!(bloom.insertions(), 1);
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let bloom = Arc::new(ConcurrentBloomFilter::new(10_000, 0.01));
+        let writers: Vec<_> = (0..8)
+            .map(|t| {
+                let bloom = Arc::clone(&bloom);
+                thread::spawn(move || {
+                    for i in (t * 1000)..(t + 1) * 1000 {
+                        bloom.insert(&i);
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(bloom.insertions(), 8000);
+        assert!((0..8000).all(|i| bloom.contains(&i)));
+    }
+
+    #[test]
+    fn matches_bloom_filter() {
+        let concurrent = ConcurrentBloomFilter::with_seeds(1000, 0.01, 1, 2);
+        let mut bloom = BloomFilter::with_seeds(1000, 0.01, 1, 2);
+        for i in 0..700 {
+            concurrent.insert(&i);
+            bloom.insert(&i);
+        }
+
+        for i in 0..5000 {
+            assert_eq!(concurrent.contains(&i), bloom.contains(&i));
+        }
+
+        let converted = BloomFilter::from(concurrent);
+        assert_eq!(converted.bitmap, bloom.bitmap);
+        assert_eq!(converted.insertions(), 700);
+
+        let round_trip = ConcurrentBloomFilter::from(bloom);
+        assert!((0..700).all(|i| round_trip.contains(&i)));
+        assert_eq!(BloomFilter::from(round_trip).bitmap, converted.bitmap);
+    }
+}
@@ -0,0 +1,98 @@
+// the constructors shared by the filters whose two hash functions are
+// keyed by `SeededState`s, written once here.
+//
+//     seeded_constructors!(Filter(items_count: usize, fp_rate: f64));
+//
+// implements `new`, `try_new`, `with_seeds`, `try_with_seeds` and `seeds`
+// for `Filter<T>`, and `with_hashers` for `Filter<T, S>`, in terms of
+// `Filter::try_with_hashers(items_count, fp_rate, hasher1, hasher2)`. the
+// hash builders must satisfy any extra bound `try_with_hashers` puts on
+// them, given as `where S: Clone`.
+//
+// a filter whose `try_with_hashers` takes other parameters gives the
+// expression that builds it from the arguments and two hashers instead,
+// as `=> |hasher1, hasher2| expr`, and provides its own `with_hashers`.
+// a filter without a `hashers` field gives the expression that gets its
+// seeds, as `seeds: |filter| expr`.
+macro_rules! seeded_constructors {
+    (
+        $filter:ident($($arg:ident: $ty:ty),*) $(where S: $bound:path)?
+        $(, seeds: |$this:ident| $seeds:expr)?
+    ) => {
+        seeded_constructors!(
+            $filter($($arg: $ty),*) => |hasher1, hasher2| {
+                Self::try_with_hashers($($arg,)* hasher1, hasher2)
+            }
+            $(, seeds: |$this| $seeds)?
+        );
+
+        impl<T: ?Sized, S: ::std::hash::BuildHasher $(+ $bound)?> $filter<T, S> {
+            // create a new filter like `new`, deriving its two hash functions
+            // from `hasher1` and `hasher2`.
+            //
+            // panics on the same invalid parameters as `new`.
+            pub fn with_hashers($($arg: $ty,)* hasher1: S, hasher2: S) -> Self {
+                Self::try_with_hashers($($arg,)* hasher1, hasher2)
+                    .unwrap_or_else(|err| panic!("{}", err))
+            }
+        }
+    };
+    (
+        $filter:ident($($arg:ident: $ty:ty),*) => |$hasher1:ident, $hasher2:ident| $build:expr
+    ) => {
+        seeded_constructors!(
+            $filter($($arg: $ty),*) => |$hasher1, $hasher2| $build,
+            seeds: |filter| (filter.hashers[0].seed(), filter.hashers[1].seed())
+        );
+    };
+    (
+        $filter:ident($($arg:ident: $ty:ty),*) => |$hasher1:ident, $hasher2:ident| $build:expr,
+        seeds: |$this:ident| $seeds:expr
+    ) => {
+        impl<T: ?Sized> $filter<T> {
+            // create a new filter from the parameters described on the type,
+            // keying its hash functions by random seeds.
+            //
+            // panics on invalid parameters; see `try_new`.
+            pub fn new($($arg: $ty),*) -> Self {
+                Self::try_new($($arg),*).unwrap_or_else(|err| panic!("{}", err))
+            }
+
+            // create a new filter like `new`, returning an error instead of
+            // panicking on invalid parameters.
+            pub fn try_new($($arg: $ty),*) -> Result<Self, $crate::BloomError> {
+                let $hasher1 = $crate::SeededState::new();
+                let $hasher2 = $crate::SeededState::new();
+                $build
+            }
+
+            // create a new filter like `new`, whose two hash functions are
+            // keyed by `seed1` and `seed2`. see `BloomFilter::with_seeds` for
+            // which filters hash items alike.
+            //
+            // panics on the same invalid parameters as `new`.
+            pub fn with_seeds($($arg: $ty,)* seed1: u64, seed2: u64) -> Self {
+                Self::try_with_seeds($($arg,)* seed1, seed2)
+                    .unwrap_or_else(|err| panic!("{}", err))
+            }
+
+            // create a new filter like `with_seeds`, returning an error
+            // instead of panicking on invalid parameters.
+            pub fn try_with_seeds(
+                $($arg: $ty,)*
+                seed1: u64,
+                seed2: u64,
+            ) -> Result<Self, $crate::BloomError> {
+                let $hasher1 = $crate::SeededState::with_seed(seed1);
+                let $hasher2 = $crate::SeededState::with_seed(seed2);
+                $build
+            }
+
+            // get the seeds the hash functions are keyed by.
+            pub fn seeds(&self) -> (u64, u64) {
+                let $this = self;
+                $seeds
+            }
+        }
+    };
+}
@@ -0,0 +1,232 @@
+use super::{get_index, hash_kernel, parameters, BloomError, SeededState};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// the number of bits in each counter of a CountingBloomFilter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterWidth {
+    Four,
+    Eight,
+    Sixteen,
+}
+
+impl CounterWidth {
+    // get the number of bits in a counter.
+    pub fn bits(self) -> u32 {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+            CounterWidth::Sixteen => 16,
+        }
+    }
+
+    // get the largest value a counter can hold. a counter that reaches it
+    // is saturated and keeps that value for the lifetime of the filter.
+    pub fn max_count(self) -> u32 {
+        (1 << self.bits()) - 1
+    }
+}
+
+// a Bloom filter with a small counter in place of each bit, so items can be
+// removed as well as inserted. created for `items_count` items at a false
+// positive rate of `fp_rate`, with counters of `width` bits, it is sized and
+// hashes like `BloomFilter`.
+//
+// removing an item that was never inserted can cause false negatives for
+// other items; only remove items known to be in the set.
+pub struct CountingBloomFilter<T: ?Sized, S = SeededState> {
+    counters: Vec<u64>,
+    width: CounterWidth,
+    optimal_m: usize,
+    optimal_k: u32,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(CountingBloomFilter(
+    items_count: usize,
+    fp_rate: f64,
+    width: CounterWidth
+));
+
+impl<T: ?Sized, S: BuildHasher> CountingBloomFilter<T, S> {
+    // create a new CountingBloomFilter like `new`, deriving its two hash
+    // functions from `hasher1` and `hasher2`, returning an error instead of
+    // panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        width: CounterWidth,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        let (optimal_m, optimal_k) = parameters(items_count, fp_rate)?;
+        let per_word = (64 / width.bits()) as usize;
+        let words = optimal_m
+            .checked_next_multiple_of(per_word)
+            .ok_or(BloomError::BitmapTooLarge)?
+            / per_word;
+
+        Ok(CountingBloomFilter {
+            counters: vec![0; words],
+            width,
+            optimal_m,
+            optimal_k,
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the width of the counters.
+    pub fn counter_width(&self) -> CounterWidth {
+        self.width
+    }
+
+    // insert items into the set.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let max = self.width.max_count();
+
+        for index in self.indexes(item) {
+            let count = self.counter(index);
+            if count < max {
+                self.set_counter(index, count + 1);
+            }
+        }
+    }
+
+    // remove an item from the set, returning whether it was present.
+    // saturated counters are never decremented, since the filter no longer
+    // knows how many items share them; this keeps removal from introducing
+    // false negatives at the cost of a few stuck counters.
+    pub fn remove(&mut self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        if !self.contains(item) {
+            return false;
+        }
+
+        let max = self.width.max_count();
+
+        for index in self.indexes(item) {
+            let count = self.counter(index);
+            if count < max {
+                self.set_counter(index, count - 1);
+            }
+        }
+
+        true
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.indexes(item).all(|index| self.counter(index) > 0)
+    }
+
+    // estimate how many times an item has been inserted (and not removed).
+    // the estimate never undercounts an unsaturated item, but it can
+    // overcount when other items share all of its counters.
+    pub fn count_estimate(&self, item: &T) -> u32
+    where
+        T: Hash,
+    {
+        self.indexes(item).map(|index| self.counter(index)).min().unwrap_or(0)
+    }
+
+    // get the indexes of the counters of `item`.
+    fn indexes(&self, item: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+        let m = self.optimal_m;
+
+        (0..self.optimal_k).map(move |k_i| get_index(h1, h2, k_i as u64, m))
+    }
+
+    // get the value of the counter at `index`.
+    fn counter(&self, index: usize) -> u32 {
+        let (word, shift) = self.position(index);
+        ((self.counters[word] >> shift) & self.width.max_count() as u64) as u32
+    }
+
+    // set the counter at `index` to `count`.
+    fn set_counter(&mut self, index: usize, count: u32) {
+        let (word, shift) = self.position(index);
+        let mask = (self.width.max_count() as u64) << shift;
+        self.counters[word] = (self.counters[word] & !mask) | (count as u64) << shift;
+    }
+
+    // get the word holding the counter at `index` and its offset in the word.
+    fn position(&self, index: usize) -> (usize, u32) {
+        let per_word = (64 / self.width.bits()) as usize;
+        (index / per_word, (index % per_word) as u32 * self.width.bits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        for width in [CounterWidth::Four, CounterWidth::Eight, CounterWidth::Sixteen] {
+            let mut bloom = CountingBloomFilter::new(100, 0.01, width);
+            bloom.insert("item_1");
+            bloom.insert("item_2");
+            assert!(bloom.contains("item_1"));
+            assert!(bloom.contains("item_2"));
+
+            assert!(bloom.remove("item_1"));
+            assert!(!bloom.contains("item_1"));
+            assert!(bloom.contains("item_2"));
+            assert!(!bloom.remove("item_1"));
+        }
+    }
+
+    #[test]
+    fn count_estimate() {
+        let mut bloom = CountingBloomFilter::with_seeds(100, 0.01, CounterWidth::Eight, 1, 2);
+        assert_eq!(bloom.count_estimate("item"), 0);
+        for _ in 0..3 {
+            bloom.insert("item");
+        }
+        assert_eq!(bloom.count_estimate("item"), 3);
+
+        bloom.remove("item");
+        assert_eq!(bloom.count_estimate("item"), 2);
+    }
+
+    #[test]
+    fn saturated_counters_never_cause_false_negatives() {
+        let mut bloom = CountingBloomFilter::with_seeds(100, 0.01, CounterWidth::Four, 1, 2);
+        for _ in 0..20 {
+            bloom.insert("item");
+        }
+        assert_eq!(bloom.count_estimate("item"), CounterWidth::Four.max_count());
+
+        for _ in 0..20 {
+            assert!(bloom.remove("item"));
+        }
+        assert!(bloom.contains("item"));
+    }
+
+    #[test]
+    fn many_items() {
+        let mut bloom = CountingBloomFilter::new(1000, 0.01, CounterWidth::Four);
+        for i in 0..1000 {
+            bloom.insert(&i);
+        }
+        for i in (0..1000).step_by(2) {
+            assert!(bloom.remove(&i));
+        }
+        assert!((1..1000).step_by(2).all(|i| bloom.contains(&i)));
+    }
+}
@@ -0,0 +1,433 @@
+use super::{hash_kernel, validate, BloomError, SeededState};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// the bucket size `new` uses; with four slots per bucket a cuckoo filter
+// can be filled to about 95% before inserts start failing.
+const DEFAULT_BUCKET_SIZE: usize = 4;
+const MAX_LOAD_FACTOR: f64 = 0.95;
+// how many fingerprints an insert may relocate before giving up.
+const MAX_KICKS: usize = 500;
+
+// a cuckoo filter (Fan et al., "Cuckoo Filter: Practically Better Than
+// Bloom", 2014). it stores a short fingerprint of each item in one of two
+// candidate buckets, relocating fingerprints between their candidates to
+// make room. unlike a Bloom filter it supports removal, and below a false
+// positive rate of about 3% it needs less space per item.
+//
+// `new(items_count, fp_rate)` creates a filter that expects to store
+// `items_count` items with a false positive rate of `fp_rate`, using
+// buckets of four fingerprints. it fails on the same parameters as
+// `BloomFilter::new`, and if `fp_rate` is too small to reach with 16-bit
+// fingerprints.
+//
+// removing an item that was never inserted can remove the fingerprint of
+// another item; only remove items known to be in the set.
+pub struct CuckooFilter<T: ?Sized, S = SeededState> {
+    // the fingerprints, `fingerprint_bits` bits each, packed into words.
+    // slot j of bucket i is fingerprint `i * bucket_size + j`; zero marks an
+    // empty slot.
+    slots: Vec<u64>,
+    bucket_count: usize,
+    bucket_size: usize,
+    fingerprint_bits: u32,
+    len: usize,
+    // xorshift state choosing which fingerprint to relocate.
+    rng: u64,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(
+    CuckooFilter(items_count: usize, fp_rate: f64) => |hasher1, hasher2| {
+        let fingerprint_bits = fingerprint_bits(items_count, fp_rate, DEFAULT_BUCKET_SIZE)?;
+        Self::try_with_hashers(
+            items_count,
+            fingerprint_bits,
+            DEFAULT_BUCKET_SIZE,
+            hasher1,
+            hasher2,
+        )
+    }
+);
+
+impl<T: ?Sized> CuckooFilter<T> {
+    // create a new CuckooFilter that expects to store `items_count` items in
+    // buckets of `bucket_size` fingerprints of `fingerprint_bits` bits each.
+    // the false positive rate is about 2 * bucket_size / 2^fingerprint_bits.
+    // the hash functions are keyed by random seeds.
+    //
+    // panics if `items_count` is zero, if `fingerprint_bits` is not between
+    // 1 and 16, or if `bucket_size` is not between 1 and 16.
+    pub fn with_params(items_count: usize, fingerprint_bits: u32, bucket_size: usize) -> Self {
+        Self::try_with_params(items_count, fingerprint_bits, bucket_size)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new CuckooFilter like `with_params`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_params(
+        items_count: usize,
+        fingerprint_bits: u32,
+        bucket_size: usize,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_hashers(
+            items_count,
+            fingerprint_bits,
+            bucket_size,
+            SeededState::new(),
+            SeededState::new(),
+        )
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> CuckooFilter<T, S> {
+    // create a new CuckooFilter like `with_params`, deriving its two hash
+    // functions from `hasher1` and `hasher2`: the first picks the bucket
+    // and the second the fingerprint.
+    //
+    // panics on the same invalid parameters as `with_params`.
+    pub fn with_hashers(
+        items_count: usize,
+        fingerprint_bits: u32,
+        bucket_size: usize,
+        hasher1: S,
+        hasher2: S,
+    ) -> Self {
+        Self::try_with_hashers(items_count, fingerprint_bits, bucket_size, hasher1, hasher2)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new CuckooFilter like `with_hashers`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fingerprint_bits: u32,
+        bucket_size: usize,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        if items_count == 0 {
+            return Err(BloomError::ZeroItemsCount);
+        }
+        if !(1..=16).contains(&fingerprint_bits) {
+            return Err(BloomError::InvalidFingerprintBits(fingerprint_bits));
+        }
+        if !(1..=16).contains(&bucket_size) {
+            return Err(BloomError::InvalidBucketSize(bucket_size));
+        }
+
+        // the alternate bucket is found by xor-ing the bucket index, so the
+        // number of buckets must be a power of two.
+        let buckets = (items_count as f64 / (bucket_size as f64 * MAX_LOAD_FACTOR)).ceil();
+        let bucket_count = (buckets as usize)
+            .checked_next_power_of_two()
+            .filter(|_| buckets < usize::MAX as f64)
+            .ok_or(BloomError::BitmapTooLarge)?;
+        let bits = bucket_count
+            .checked_mul(bucket_size)
+            .and_then(|slots| slots.checked_mul(fingerprint_bits as usize))
+            .ok_or(BloomError::BitmapTooLarge)?;
+
+        Ok(CuckooFilter {
+            slots: vec![0; bits.div_ceil(64)],
+            bucket_count,
+            bucket_size,
+            fingerprint_bits,
+            len: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of items in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // check if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // get the number of fingerprints the filter has room for. inserts
+    // usually start failing somewhat before it is reached.
+    pub fn capacity(&self) -> usize {
+        self.bucket_count * self.bucket_size
+    }
+
+    // get the number of bits in a fingerprint.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    // get the number of fingerprints in a bucket.
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    // insert items into the set. inserting an item twice stores it twice,
+    // so it must be removed twice.
+    //
+    // returns `BloomError::FilterFull`, leaving the filter unchanged, when
+    // no room can be made for the item.
+    pub fn insert(&mut self, item: &T) -> Result<(), BloomError>
+    where
+        T: Hash,
+    {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        if self.try_put(i1, fingerprint) || self.try_put(i2, fingerprint) {
+            self.len += 1;
+            return Ok(());
+        }
+
+        // evict fingerprints from bucket to bucket, remembering where each
+        // one came from so the moves can be undone if we run out of kicks.
+        let mut fingerprint = fingerprint;
+        let mut index = if self.next_random() & 1 == 0 { i1 } else { i2 };
+        let mut path = Vec::new();
+
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() as usize % self.bucket_size;
+            let position = index * self.bucket_size + slot;
+            let evicted = self.slot(position);
+            self.set_slot(position, fingerprint);
+            path.push(position);
+
+            fingerprint = evicted;
+            index = self.alt_index(index, fingerprint);
+            if self.try_put(index, fingerprint) {
+                self.len += 1;
+                return Ok(());
+            }
+        }
+
+        for position in path.into_iter().rev() {
+            let displaced = self.slot(position);
+            self.set_slot(position, fingerprint);
+            fingerprint = displaced;
+        }
+
+        Err(BloomError::FilterFull)
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        self.find(i1, fingerprint).is_some() || self.find(i2, fingerprint).is_some()
+    }
+
+    // remove one copy of an item from the set, returning whether it was
+    // present.
+    pub fn remove(&mut self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        match self.find(i1, fingerprint).or_else(|| self.find(i2, fingerprint)) {
+            Some(position) => {
+                self.set_slot(position, 0);
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // get the first bucket and the fingerprint of `item`.
+    fn locate(&self, item: &T) -> (usize, u16)
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+        let fingerprint = (h2 >> (64 - self.fingerprint_bits)) as u16;
+
+        // zero marks an empty slot, so it cannot be a fingerprint.
+        (h1 as usize & (self.bucket_count - 1), fingerprint.max(1))
+    }
+
+    // get the other bucket a fingerprint in bucket `index` may live in.
+    // applying it twice gives back `index`, so fingerprints can be moved
+    // without knowing the item they came from.
+    fn alt_index(&self, index: usize, fingerprint: u16) -> usize {
+        let hash = (fingerprint as u64).wrapping_mul(0xc6a4_a793_5bd1_e995);
+        (index ^ hash as usize) & (self.bucket_count - 1)
+    }
+
+    // put `fingerprint` in an empty slot of bucket `index`, if there is one.
+    fn try_put(&mut self, index: usize, fingerprint: u16) -> bool {
+        match self.find(index, 0) {
+            Some(position) => {
+                self.set_slot(position, fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // find the position of `fingerprint` in bucket `index`.
+    fn find(&self, index: usize, fingerprint: u16) -> Option<usize> {
+        let start = index * self.bucket_size;
+        (start..start + self.bucket_size).find(|&position| self.slot(position) == fingerprint)
+    }
+
+    // get the fingerprint at `position`.
+    fn slot(&self, position: usize) -> u16 {
+        let bit = position * self.fingerprint_bits as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.slots[word] >> offset;
+
+        if offset + self.fingerprint_bits as usize > 64 {
+            value |= self.slots[word + 1] << (64 - offset);
+        }
+
+        (value & self.fingerprint_mask()) as u16
+    }
+
+    // set the fingerprint at `position`.
+    fn set_slot(&mut self, position: usize, fingerprint: u16) {
+        let bit = position * self.fingerprint_bits as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mask = self.fingerprint_mask();
+        let value = fingerprint as u64;
+
+        self.slots[word] = (self.slots[word] & !(mask << offset)) | value << offset;
+
+        if offset + self.fingerprint_bits as usize > 64 {
+            let shift = 64 - offset;
+            self.slots[word + 1] = (self.slots[word + 1] & !(mask >> shift)) | value >> shift;
+        }
+    }
+
+    fn fingerprint_mask(&self) -> u64 {
+        (1 << self.fingerprint_bits) - 1
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+// calculate the fingerprint size that keeps the false positive rate of
+// buckets of `bucket_size` below `fp_rate`: an item is compared against
+// 2 * bucket_size fingerprints, each matching with probability 2^-f.
+fn fingerprint_bits(
+    items_count: usize,
+    fp_rate: f64,
+    bucket_size: usize,
+) -> Result<u32, BloomError> {
+    validate(items_count, fp_rate)?;
+
+    let bits = (2.0 * bucket_size as f64 / fp_rate).log2().ceil();
+    if bits > 16.0 {
+        return Err(BloomError::FpRateOutOfRange(fp_rate));
+    }
+
+    Ok(bits as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut cuckoo = CuckooFilter::new(100, 0.01);
+        assert!(cuckoo.is_empty());
+        cuckoo.insert("item_1").unwrap();
+        cuckoo.insert("item_2").unwrap();
+        assert!(cuckoo.contains("item_1"));
+        assert!(cuckoo.contains("item_2"));
+        assert_eq!(cuckoo.len(), 2);
+
+        assert!(cuckoo.remove("item_1"));
+        assert!(!cuckoo.contains("item_1"));
+        assert!(cuckoo.contains("item_2"));
+        assert!(!cuckoo.remove("item_1"));
+        assert_eq!(cuckoo.len(), 1);
+    }
+
+    #[test]
+    fn duplicates_are_counted() {
+        let mut cuckoo = CuckooFilter::new(100, 0.01);
+        cuckoo.insert("item").unwrap();
+        cuckoo.insert("item").unwrap();
+
+        assert!(cuckoo.remove("item"));
+        assert!(cuckoo.contains("item"));
+        assert!(cuckoo.remove("item"));
+        assert!(!cuckoo.contains("item"));
+    }
+
+    #[test]
+    fn parameters() {
+        let cuckoo: CuckooFilter<i32> = CuckooFilter::new(1000, 0.01);
+        assert_eq!(cuckoo.bucket_size(), 4);
+        assert_eq!(cuckoo.fingerprint_bits(), 10);
+        assert!(cuckoo.capacity() >= 1000);
+
+        let cuckoo: CuckooFilter<i32> = CuckooFilter::with_params(1000, 13, 2);
+        assert_eq!(cuckoo.fingerprint_bits(), 13);
+        assert_eq!(cuckoo.bucket_size(), 2);
+
+        assert!(CuckooFilter::<i32>::try_new(1000, 1e-6).is_err());
+        assert_eq!(
+            CuckooFilter::<i32>::try_with_params(1000, 17, 4).err(),
+            Some(BloomError::InvalidFingerprintBits(17))
+        );
+        assert_eq!(
+            CuckooFilter::<i32>::try_with_params(1000, 8, 0).err(),
+            Some(BloomError::InvalidBucketSize(0))
+        );
+    }
+
+    #[test]
+    fn fills_up() {
+        let mut cuckoo = CuckooFilter::with_hashers(
+            1000,
+            12,
+            4,
+            SeededState::with_seed(1),
+            SeededState::with_seed(2),
+        );
+        let mut inserted = 0;
+        let (err, before) = loop {
+            let before = cuckoo.slots.clone();
+            match cuckoo.insert(&inserted) {
+                Ok(()) => inserted += 1,
+                Err(err) => break (err, before),
+            }
+        };
+
+        assert_eq!(err, BloomError::FilterFull);
+        assert!(inserted as f64 > cuckoo.capacity() as f64 * 0.9);
+        assert_eq!(cuckoo.len(), inserted as usize);
+        // the failed insert undid its kicks, so nothing was lost.
+        assert_eq!(cuckoo.slots, before);
+        assert!((0..inserted).all(|i| cuckoo.contains(&i)));
+    }
+
+    #[test]
+    fn meets_fp_rate() {
+        crate::assert_fp_rate(
+            &mut CuckooFilter::with_seeds(10_000, 0.01, 1, 2),
+            |cuckoo, i| cuckoo.insert(&i).unwrap(),
+            |cuckoo, i| cuckoo.contains(&i),
+            0.01,
+        );
+    }
+}
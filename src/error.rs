@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::fmt;
+
+// the error returned when a filter cannot be created or combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BloomError {
+    // `items_count` is zero, which would make the bitmap empty.
+    ZeroItemsCount,
+    // `fp_rate` is NaN.
+    FpRateNotANumber,
+    // `fp_rate` is not strictly between 0 and 1.
+    FpRateOutOfRange(f64),
+    // the bitmap needed for `items_count` and `fp_rate` has more bits than
+    // fit in a `usize`.
+    BitmapTooLarge,
+    // the filters being combined have bitmaps of different sizes
+    // (`optimal_m` of each filter).
+    MismatchedOptimalM(usize, usize),
+    // the filters being combined use different numbers of hash functions
+    // (`optimal_k` of each filter).
+    MismatchedOptimalK(u32, u32),
+    // the filters being combined hash items differently, e.g. because they
+    // are keyed by different seeds.
+    MismatchedHashers,
+    // the filters being merged keep fingerprints of different sizes (the
+    // fingerprint bits of each filter).
+    MismatchedFingerprintBits(u32, u32),
+    // a bitset of this many bytes is not a whole, non-zero number of blocks.
+    InvalidBitsetLength(usize),
+    // a fingerprint of this many bits is not supported.
+    InvalidFingerprintBits(u32),
+    // a bucket of this many fingerprints is not supported.
+    InvalidBucketSize(usize),
+    // the filter has no room left for the item.
+    FilterFull,
+    // a filter parameter is out of range; the message names it.
+    InvalidParameter(&'static str),
+    // no seed was found that lets a static filter hold all of its keys.
+    ConstructionFailed,
+}
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomError::ZeroItemsCount => f.write_str("items_count must be greater than zero"),
+            BloomError::FpRateNotANumber => f.write_str("fp_rate must not be NaN"),
+            BloomError::FpRateOutOfRange(fp_rate) => write!(
+                f,
+                "fp_rate must be greater than 0 and less than 1, got {}",
+                fp_rate
+            ),
+            BloomError::BitmapTooLarge => {
+                f.write_str("bitmap for items_count and fp_rate does not fit in memory")
+            }
+            BloomError::MismatchedOptimalM(left, right) => write!(
+                f,
+                "filters have different bitmap sizes ({} and {} bits)",
+                left, right
+            ),
+            BloomError::MismatchedOptimalK(left, right) => write!(
+                f,
+                "filters have different numbers of hash functions ({} and {})",
+                left, right
+            ),
+            BloomError::MismatchedHashers => f.write_str("filters use different hash functions"),
+            BloomError::MismatchedFingerprintBits(left, right) => write!(
+                f,
+                "filters have different fingerprint sizes ({} and {} bits)",
+                left, right
+            ),
+            BloomError::InvalidBitsetLength(len) => {
+                write!(f, "bitset of {} bytes is not a whole number of blocks", len)
+            }
+            BloomError::InvalidFingerprintBits(bits) => {
+                write!(f, "fingerprints of {} bits are not supported", bits)
+            }
+            BloomError::InvalidBucketSize(size) => {
+                write!(f, "buckets must hold 1 to 16 fingerprints, got {}", size)
+            }
+            BloomError::FilterFull => f.write_str("filter is full"),
+            BloomError::InvalidParameter(message) => f.write_str(message),
+            BloomError::ConstructionFailed => {
+                f.write_str("could not construct a static filter from the keys")
+            }
+        }
+    }
+}
+
+impl Error for BloomError {}
@@ -0,0 +1,191 @@
+use super::{BloomError, BloomFilter, BloomHash, SeededState};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+// a collection of BloomFilters sharing the same hashers, e.g. one per data
+// file, that finds which of them may contain an item.
+//
+// an item is hashed once per query, however many filters there are. the
+// filters may differ in size and false positive rate, but must hash items
+// alike, which `push` checks.
+pub struct FilterSet<T: ?Sized, S = SeededState> {
+    filters: Vec<BloomFilter<T, S>>,
+}
+
+impl<T: ?Sized, S> FilterSet<T, S> {
+    // create an empty FilterSet.
+    pub fn new() -> Self {
+        FilterSet {
+            filters: Vec::new(),
+        }
+    }
+
+    // get the number of filters.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    // check if there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    // get the filter at `index`.
+    pub fn get(&self, index: usize) -> Option<&BloomFilter<T, S>> {
+        self.filters.get(index)
+    }
+
+    // get the filter at `index` to insert into.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut BloomFilter<T, S>> {
+        self.filters.get_mut(index)
+    }
+
+    // get the filters, in the order they were added.
+    pub fn filters(&self) -> &[BloomFilter<T, S>] {
+        &self.filters
+    }
+
+    // take the filters out of the set.
+    pub fn into_filters(self) -> Vec<BloomFilter<T, S>> {
+        self.filters
+    }
+}
+
+impl<T: ?Sized, S> Default for FilterSet<T, S> {
+    fn default() -> Self {
+        FilterSet::new()
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + PartialEq> FilterSet<T, S> {
+    // create a FilterSet of `filters`, returning
+    // `BloomError::MismatchedHashers` if they do not all share hashers.
+    pub fn from_filters(filters: Vec<BloomFilter<T, S>>) -> Result<Self, BloomError> {
+        check_hashers(&filters)?;
+        Ok(FilterSet { filters })
+    }
+
+    // add a filter to the set, returning its index, or
+    // `BloomError::MismatchedHashers` if it hashes items differently from
+    // the filters already in the set.
+    pub fn push(&mut self, filter: BloomFilter<T, S>) -> Result<usize, BloomError> {
+        if let Some(first) = self.filters.first() {
+            if first.hashers != filter.hashers {
+                return Err(BloomError::MismatchedHashers);
+            }
+        }
+
+        self.filters.push(filter);
+        Ok(self.filters.len() - 1)
+    }
+
+    // get the indexes of the filters that may contain an item, in
+    // ascending order. false positives are possible, but not false
+    // negatives.
+    pub fn contains<Q>(&self, item: &Q) -> Vec<usize>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        match self.filters.first() {
+            Some(first) => candidates(&self.filters, first.hash(item)),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + PartialEq> BloomFilter<T, S> {
+    // get the indexes of the filters among `filters` that may contain an
+    // item, hashing it once for all of them. returns
+    // `BloomError::MismatchedHashers` if the filters do not all share
+    // hashers; a `FilterSet` checks this once instead of on every query.
+    pub fn contains_in<Q>(filters: &[Self], item: &Q) -> Result<Vec<usize>, BloomError>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        check_hashers(filters)?;
+
+        Ok(match filters.first() {
+            Some(first) => candidates(filters, first.hash(item)),
+            None => Vec::new(),
+        })
+    }
+}
+
+fn check_hashers<T: ?Sized, S: PartialEq>(filters: &[BloomFilter<T, S>]) -> Result<(), BloomError> {
+    match filters.split_first() {
+        Some((first, rest)) if rest.iter().any(|filter| filter.hashers != first.hashers) => {
+            Err(BloomError::MismatchedHashers)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn candidates<T: ?Sized, S: BuildHasher>(
+    filters: &[BloomFilter<T, S>],
+    hash: BloomHash,
+) -> Vec<usize> {
+    filters
+        .iter()
+        .enumerate()
+        .filter(|(_, filter)| filter.contains_bloom_hash(hash))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> FilterSet<u64> {
+        let mut set = FilterSet::new();
+        for file in 0..1000u64 {
+            // files of different sizes, each holding 100 keys.
+            let mut bloom = BloomFilter::with_seeds(100 + file as usize, 0.01, 1, 2);
+            for key in file * 100..(file + 1) * 100 {
+                bloom.insert(&key);
+            }
+            assert_eq!(set.push(bloom), Ok(file as usize));
+        }
+        set
+    }
+
+    #[test]
+    fn finds_candidate_filters() {
+        let set = files();
+
+        for key in [0, 12_345, 99_999] {
+            let candidates = set.contains(&key);
+            assert!(candidates.contains(&(key as usize / 100)));
+            assert!(candidates.len() < 40);
+        }
+        assert_eq!(
+            BloomFilter::contains_in(set.filters(), &12_345).unwrap(),
+            set.contains(&12_345)
+        );
+    }
+
+    #[test]
+    fn requires_shared_hashers() {
+        let mut set = files();
+        let other = BloomFilter::with_seeds(100, 0.01, 1, 3);
+        assert_eq!(set.push(other), Err(BloomError::MismatchedHashers));
+
+        let mut filters = set.into_filters();
+        filters.push(BloomFilter::with_seeds(100, 0.01, 3, 2));
+        assert_eq!(
+            BloomFilter::contains_in(&filters, &1).err(),
+            Some(BloomError::MismatchedHashers)
+        );
+        assert!(FilterSet::from_filters(filters).is_err());
+    }
+
+    #[test]
+    fn empty() {
+        let set: FilterSet<str> = FilterSet::default();
+        assert!(set.is_empty());
+        assert!(set.contains("item").is_empty());
+        assert_eq!(BloomFilter::<str>::contains_in(&[], "item"), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,43 @@
+use siphasher::sip::SipHasher13;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// a `BuildHasher` that creates SipHash-1-3 hashers keyed by a seed.
+// unlike `RandomState`, the seed can be read back, so hashes computed by
+// one process can be reproduced by another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    // create a new SeededState keyed by a random seed.
+    pub fn new() -> Self {
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+
+    // create a new SeededState keyed by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        SeededState { seed }
+    }
+
+    // get the seed the hashers are keyed by.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for SeededState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.seed, 0)
+    }
+}
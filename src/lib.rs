@@ -1,61 +1,265 @@
 extern crate bit_vec;
+extern crate siphasher;
+extern crate xxhash_rust;
+
+#[macro_use]
+mod constructors;
+
+mod bitsliced;
+mod blocked;
+mod cardinality;
+mod concurrent;
+mod counting;
+mod cuckoo;
+mod error;
+mod filter_set;
+mod hasher;
+mod ops;
+mod partitioned;
+mod prehashed;
+mod quotient;
+mod scalable;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod sliding;
+mod split_block;
+mod stable;
+mod stats;
+mod wire;
+mod xor;
+
+pub use bit_vec::BitVec;
+pub use bitsliced::BitSlicedBloomIndex;
+pub use blocked::BlockedBloomFilter;
+pub use concurrent::ConcurrentBloomFilter;
+pub use counting::{CounterWidth, CountingBloomFilter};
+pub use cuckoo::CuckooFilter;
+pub use error::BloomError;
+pub use filter_set::FilterSet;
+pub use hasher::SeededState;
+pub use partitioned::PartitionedBloomFilter;
+pub use prehashed::BloomHash;
+pub use quotient::QuotientFilter;
+pub use scalable::ScalableBloomFilter;
+pub use sliding::{Clock, MonotonicClock, SlidingWindowBloomFilter};
+pub use split_block::{ParquetValue, SplitBlockBloomFilter};
+pub use stable::StableBloomFilter;
+pub use stats::BloomStats;
+pub use wire::WireError;
+pub use xor::{
+    BinaryFuseFilter, BinaryFuseFilter16, BinaryFuseFilter8, Fingerprint, XorFilter, XorFilter16,
+    XorFilter8,
+};
 
-use bit_vec::BitVec;
 use core::f64;
-use std::collections::hash_map::{DefaultHasher, RandomState};
-use std::hash::{BuildHasher, Hash, Hasher};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 
 
-pub struct BloomFilter<T: ?Sized> {
+pub struct BloomFilter<T: ?Sized, S = SeededState> {
     bitmap: BitVec,
     optimal_m: usize,
     optimal_k: u32,
-    hashers: [DefaultHasher; 2],
-    _marker: PhantomData<T>
+    fp_rate: f64,
+    insertions: u64,
+    hashers: [S; 2],
+    // the filter never owns a `T`, so it is `Send` and `Sync` whenever `S` is,
+    // regardless of `T`, and covariant in `T` like a `PhantomData<T>`.
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T: ?Sized, S: Clone> Clone for BloomFilter<T, S> {
+    fn clone(&self) -> Self {
+        BloomFilter {
+            bitmap: self.bitmap.clone(),
+            optimal_m: self.optimal_m,
+            optimal_k: self.optimal_k,
+            fp_rate: self.fp_rate,
+            insertions: self.insertions,
+            hashers: self.hashers.clone(),
+            _marker: PhantomData
+        }
+    }
 }
 
 impl<T: ?Sized> BloomFilter<T> {
     // create a new BloomFilter that expects to store `items_count`
     // membership with a false positive rate of the value specified in `fp_rate`.
+    // the hash functions are keyed by random seeds.
+    //
+    // panics if `items_count` is zero, if `fp_rate` is not strictly between
+    // 0 and 1, or if the bitmap would not fit in memory; see `try_new`.
     pub fn new(items_count: usize, fp_rate: f64) -> Self {
-        let optimal_m = Self::bitmap_size(items_count, fp_rate);
-        let optimal_k = Self::optimal_k(fp_rate);
-        let hashers = [
-            RandomState::new().build_hasher(),
-            RandomState::new().build_hasher(),
-        ];
+        Self::with_hashers(items_count, fp_rate, SeededState::new(), SeededState::new())
+    }
 
-        BloomFilter {
-            bitmap: BitVec::from_elem(optimal_m as usize, false),
+    // create a new BloomFilter like `new`, returning an error instead of
+    // panicking on invalid parameters.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        Self::try_with_hashers(items_count, fp_rate, SeededState::new(), SeededState::new())
+    }
+
+    // create a new BloomFilter whose two hash functions are keyed by `seed1`
+    // and `seed2`. filters created with the same parameters and seeds set the
+    // same bits for the same items in any process built by the same Rust
+    // release for a target of the same pointer width. `usize`, `isize` and
+    // the lengths of slices and `Vec`s hash at the pointer width, and std's
+    // `Hash` impls may change between releases; to share filters more
+    // widely, hash items portably and use `insert_hash` and `contains_hash`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_seeds(items_count: usize, fp_rate: f64, seed1: u64, seed2: u64) -> Self {
+        Self::with_hashers(
+            items_count,
+            fp_rate,
+            SeededState::with_seed(seed1),
+            SeededState::with_seed(seed2),
+        )
+    }
+
+    // create a new BloomFilter like `with_seeds`, returning an error instead
+    // of panicking on invalid parameters.
+    pub fn try_with_seeds(
+        items_count: usize,
+        fp_rate: f64,
+        seed1: u64,
+        seed2: u64,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_hashers(
+            items_count,
+            fp_rate,
+            SeededState::with_seed(seed1),
+            SeededState::with_seed(seed2),
+        )
+    }
+
+    // get the seeds the hash functions are keyed by.
+    pub fn seeds(&self) -> (u64, u64) {
+        (self.hashers[0].seed(), self.hashers[1].seed())
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> BloomFilter<T, S> {
+    // create a new BloomFilter like `new`, deriving its two hash functions
+    // from `hasher1` and `hasher2`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_hashers(items_count: usize, fp_rate: f64, hasher1: S, hasher2: S) -> Self {
+        Self::try_with_hashers(items_count, fp_rate, hasher1, hasher2)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new BloomFilter like `with_hashers`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        let (optimal_m, optimal_k) = parameters(items_count, fp_rate)?;
+
+        Ok(BloomFilter {
+            bitmap: BitVec::from_elem(optimal_m, false),
             optimal_m,
             optimal_k,
-            hashers,
+            fp_rate,
+            insertions: 0,
+            hashers: [hasher1, hasher2],
             _marker: PhantomData
-        }
+        })
+    }
+
+    // get the hash builders the two hash functions are derived from.
+    pub fn hashers(&self) -> (&S, &S) {
+        (&self.hashers[0], &self.hashers[1])
+    }
+
+    // get the false positive rate the filter was created for.
+    pub fn fp_rate(&self) -> f64 {
+        self.fp_rate
+    }
+
+    // get the number of times `insert` has been called, counting repeated
+    // insertions of the same item.
+    pub fn insertions(&self) -> u64 {
+        self.insertions
+    }
+
+    // insert items into the set. like `HashSet`, the item can be given in
+    // any borrowed form of `T`: a `BloomFilter<String>` takes a `&str`. the
+    // `Borrow` contract requires both forms to hash alike, so they set the
+    // same bits.
+    pub fn insert<Q>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let (h1, h2) = self.hash_kernel(item);
+        self.set_bits(h1, h2);
     }
 
-    // insert items into the set.
-    pub fn insert(&mut self, item: &T)
+    // check if an item, in any borrowed form of `T`, is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains<Q>(&self, item: &Q) -> bool
     where
-        T: Hash,
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
     {
         let (h1, h2) = self.hash_kernel(item);
+        self.has_bits(h1, h2)
+    }
+
+    // insert an item, returning whether it was possibly present already,
+    // i.e. whether every one of its bits was set beforehand. the item is
+    // hashed once and its bits are checked and set in a single pass, unlike
+    // calling `contains` and then `insert`.
+    pub fn check_and_insert<Q>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let (h1, h2) = self.hash_kernel(item);
+        let mut present = true;
 
         for k_i in 0..self.optimal_k {
             let index = self.get_index(h1, h2, k_i as u64);
-            self.bitmap.set(index, true)
+
+            if !self.bitmap.get(index).unwrap() {
+                self.bitmap.set(index, true);
+                present = false;
+            }
         }
+
+        self.insertions += 1;
+        present
     }
 
-    // check if an item is present in the set.
-    // false positives are possible, but not false negatives.
-    pub fn contains(&mut self, item: &T) -> bool
+    // insert every item of `items` with `check_and_insert`, returning a
+    // bitmask with bit i set if the i-th item was new. an item repeated
+    // within `items` is new only the first time.
+    pub fn check_and_insert_all<'a, Q, I>(&mut self, items: I) -> BitVec
     where
-        T: Hash,
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
     {
-        let (h1, h2) = self.hash_kernel(item);
+        items.into_iter().map(|item| !self.check_and_insert(item)).collect()
+    }
+
+    // set the bits of the item hashing to `h1` and `h2`.
+    fn set_bits(&mut self, h1: u64, h2: u64) {
+        for k_i in 0..self.optimal_k {
+            let index = self.get_index(h1, h2, k_i as u64);
+            self.bitmap.set(index, true)
+        }
+
+        self.insertions += 1;
+    }
 
+    // check if all bits of the item hashing to `h1` and `h2` are set.
+    fn has_bits(&self, h1: u64, h2: u64) -> bool {
         for k_i in 0..self.optimal_k {
             let index = self.get_index(h1, h2, k_i as u64);
 
@@ -69,40 +273,114 @@ impl<T: ?Sized> BloomFilter<T> {
 
     // get the index from the hash value of `k_i`.
     fn get_index(&self, h1: u64, h2: u64, k_i: u64) -> usize {
-        h1.wrapping_add((k_i).wrapping_mul(h2)) as usize % self.optimal_m
+        get_index(h1, h2, k_i, self.optimal_m)
     }
 
-    // calculate the size of `bitmap`.
-    // the size of bitmap depends on the target false positive probability
-    // and the number of items in the set.
-    fn bitmap_size(items_count: usize, fp_rate: f64) -> usize {
-        let ln2_2 = core::f64::consts::LN_2 * core::f64::consts::LN_2;
-        ((-1.0f64 * items_count as f64 * fp_rate.ln()) / ln2_2).ceil() as usize
+    // calculate two hash values from which the k hashes are derived.
+    fn hash_kernel<Q: ?Sized + Hash>(&self, item: &Q) -> (u64, u64) {
+        hash_kernel(&self.hashers, item)
     }
+}
+
+// the sizing and hashing below is shared by every filter in the crate, so
+// filters built with the same parameters and hashers agree on their probes.
+
+// calculate `optimal_m` and `optimal_k` for a filter that expects to store
+// `items_count` items with a false positive rate of `fp_rate`.
+fn parameters(items_count: usize, fp_rate: f64) -> Result<(usize, u32), BloomError> {
+    validate(items_count, fp_rate)?;
+    let optimal_m = bitmap_size(items_count, fp_rate).ok_or(BloomError::BitmapTooLarge)?;
 
-    // calculate the number of hash functions.
-    // the required number of hash functions only depends on the target
-    // false positive probability.
-    fn optimal_k(fp_rate: f64) -> u32 {
-        ((-1.0f64 * fp_rate.ln()) / core::f64::consts::LN_2).ceil() as u32
+    Ok((optimal_m, optimal_k(fp_rate)))
+}
+
+// check that `items_count` and `fp_rate` describe a usable filter.
+fn validate(items_count: usize, fp_rate: f64) -> Result<(), BloomError> {
+    if items_count == 0 {
+        return Err(BloomError::ZeroItemsCount);
+    }
+    if fp_rate.is_nan() {
+        return Err(BloomError::FpRateNotANumber);
+    }
+    if fp_rate <= 0.0 || fp_rate >= 1.0 {
+        return Err(BloomError::FpRateOutOfRange(fp_rate));
     }
 
-    // calculate two hash values from which the k hashes are derived.
-    fn hash_kernel(&self, item: &T) -> (u64, u64)
-    where
-        T: Hash,
-    {
-        let hasher1 = &mut self.hashers[0].clone();
-        let hasher2 = &mut self.hashers[1].clone();
+    Ok(())
+}
 
-        item.hash(hasher1);
-        item.hash(hasher2);
+// calculate the size of `bitmap`.
+// the size of bitmap depends on the target false positive probability
+// and the number of items in the set. returns `None` if it does not fit
+// in a `usize`.
+fn bitmap_size(items_count: usize, fp_rate: f64) -> Option<usize> {
+    let ln2_2 = core::f64::consts::LN_2 * core::f64::consts::LN_2;
+    let size = ((-(items_count as f64) * fp_rate.ln()) / ln2_2).ceil();
 
-        let hash1 = hasher1.finish();
-        let hash2 = hasher2.finish();
+    // `usize::MAX as f64` rounds up to a power of two, so `<` is exact.
+    if size < usize::MAX as f64 {
+        Some(size as usize)
+    } else {
+        None
+    }
+}
+
+// the largest number of hash functions `optimal_k` returns, for the smallest
+// positive `fp_rate`. decoded filters claiming more are corrupt.
+const MAX_OPTIMAL_K: u32 = 1074;
+
+// calculate the number of hash functions.
+// the required number of hash functions only depends on the target
+// false positive probability.
+fn optimal_k(fp_rate: f64) -> u32 {
+    ((-fp_rate.ln()) / core::f64::consts::LN_2).ceil() as u32
+}
 
-        (hash1, hash2)
+// get the index into a bitmap of `m` bits from the hash value of `k_i`.
+fn get_index(h1: u64, h2: u64, k_i: u64, m: usize) -> usize {
+    h1.wrapping_add((k_i).wrapping_mul(h2)) as usize % m
+}
+
+// calculate two hash values from which the k hashes are derived.
+fn hash_kernel<T: ?Sized + Hash, S: BuildHasher>(hashers: &[S; 2], item: &T) -> (u64, u64) {
+    let hash1 = hashers[0].hash_one(item);
+    let hash2 = hashers[1].hash_one(item);
+
+    (hash1, hash2)
+}
+
+// pack `bitmap` into 64-bit words, bit i of the bitmap being bit (i % 64) of
+// word (i / 64). bit-vec stores bit i in bit (i % 32) of block (i / 32), so
+// two consecutive blocks make up one word.
+fn bitmap_words(bitmap: &BitVec) -> impl Iterator<Item = u64> + '_ {
+    bitmap.storage().chunks(2).map(|pair| {
+        let low = pair[0] as u64;
+        let high = pair.get(1).map_or(0, |&block| block as u64);
+        low | high << 32
+    })
+}
+
+// unpack the first `m` bits of `words`, the inverse of `bitmap_words`.
+fn bitmap_from_words(words: &[u64], m: usize) -> BitVec {
+    BitVec::from_fn(m, |i| words[i / 64] >> (i % 64) & 1 == 1)
+}
+
+// insert the items 0..10,000 into `filter`, check that they are all found,
+// and that fewer than `max_fp_rate` of the next 100,000 items are.
+#[cfg(test)]
+fn assert_fp_rate<F>(
+    filter: &mut F,
+    mut insert: impl FnMut(&mut F, i32),
+    contains: impl Fn(&F, i32) -> bool,
+    max_fp_rate: f64,
+) {
+    for i in 0..10_000 {
+        insert(filter, i);
     }
+
+    assert!((0..10_000).all(|i| contains(filter, i)));
+    let false_positives = (10_000..110_000).filter(|&i| contains(filter, i)).count();
+    assert!((false_positives as f64 / 100_000.0) < max_fp_rate);
 }
 
 #[cfg(test)]
@@ -111,18 +389,177 @@ mod tests {
 
     #[test]
     fn insert() {
-        let mut bloom = BloomFilter::new(100, 0.01);
+        let mut bloom: BloomFilter<str> = BloomFilter::new(100, 0.01);
         bloom.insert("item");
         assert!(bloom.contains("item"));
     }
 
+    #[test]
+    fn borrowed_forms() {
+        let mut bloom: BloomFilter<String> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        bloom.insert(&"owned".to_string());
+        bloom.insert("borrowed");
+        assert!(bloom.contains("owned"));
+        assert!(bloom.contains(&"borrowed".to_string()));
+        assert_eq!(bloom.hash_kernel("item"), bloom.hash_kernel(&"item".to_string()));
+
+        let mut bytes: BloomFilter<Vec<u8>> = BloomFilter::new(100, 0.01);
+        bytes.insert(&vec![1, 2, 3]);
+        assert!(bytes.contains(&[1, 2, 3][..]));
+    }
+
     #[test]
     fn check_and_insert() {
-        let mut bloom = BloomFilter::new(100, 0.01);
+        let mut bloom: BloomFilter<str> = BloomFilter::new(100, 0.01);
         assert!(!bloom.contains("item_1"));
         assert!(!bloom.contains("item_2"));
         bloom.insert("item_1");
         assert!(bloom.contains("item_1"));
         assert!(!bloom.contains("item_2"));
     }
+
+    #[test]
+    fn check_and_insert_reports_presence() {
+        let mut bloom: BloomFilter<str> = BloomFilter::new(100, 0.01);
+        assert!(!bloom.check_and_insert("item"));
+        assert!(bloom.check_and_insert("item"));
+        assert!(bloom.contains("item"));
+        assert_eq!(bloom.insertions(), 2);
+    }
+
+    #[test]
+    fn check_and_insert_all() {
+        let mut bloom: BloomFilter<i32> = BloomFilter::with_seeds(1000, 0.01, 1, 2);
+        bloom.insert(&2);
+
+        let new = bloom.check_and_insert_all(&[1, 2, 3, 1]);
+        assert_eq!(new.iter().collect::<Vec<_>>(), [true, false, true, false]);
+        assert!((1..=3).all(|i| bloom.contains(&i)));
+        assert!(bloom.check_and_insert_all(&[]).is_empty());
+    }
+
+    #[test]
+    fn same_seeds_same_hashes() {
+        let mut a: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 42, 1337);
+        let mut b: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 42, 1337);
+        assert_eq!(a.seeds(), (42, 1337));
+        assert_eq!(a.hash_kernel("item"), b.hash_kernel("item"));
+
+        a.insert("item");
+        b.insert("item");
+        assert_eq!(a.bitmap, b.bitmap);
+        assert!(b.contains("item"));
+    }
+
+    #[test]
+    fn seeded_hashes_are_stable() {
+        let bloom: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        assert_eq!(
+            bloom.hash_kernel("item"),
+            (15315638956406981987, 5335798000953786279)
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        let new = BloomFilter::<str>::try_new;
+
+        assert_eq!(new(0, 0.01).err(), Some(BloomError::ZeroItemsCount));
+        assert_eq!(new(100, f64::NAN).err(), Some(BloomError::FpRateNotANumber));
+        for fp_rate in [0.0, 1.0, -0.5, 1.5, f64::INFINITY] {
+            assert_eq!(new(100, fp_rate).err(), Some(BloomError::FpRateOutOfRange(fp_rate)));
+        }
+        assert_eq!(new(usize::MAX, 1e-300).err(), Some(BloomError::BitmapTooLarge));
+
+        let bloom = new(1, 0.5).unwrap();
+        assert!(bloom.optimal_m > 0);
+        assert_eq!(bloom.optimal_k, 1);
+        assert_eq!(optimal_k(f64::from_bits(1)), MAX_OPTIMAL_K);
+    }
+
+    #[test]
+    #[should_panic(expected = "items_count must be greater than zero")]
+    fn new_panics_on_zero_items() {
+        BloomFilter::<str>::new(0, 0.01);
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BloomFilter<str>>();
+        assert_send_sync::<BloomFilter<std::rc::Rc<u32>>>();
+        assert_send_sync::<BloomFilter<*const u8>>();
+    }
+
+    #[test]
+    fn covariant() {
+        // a filter of `&'static str` can stand in for one of a shorter
+        // lifetime.
+        fn contains<'a>(bloom: &BloomFilter<&'a str>, item: &'a str) -> bool {
+            bloom.contains(&item)
+        }
+
+        let mut bloom: BloomFilter<&'static str> = BloomFilter::new(100, 0.01);
+        bloom.insert(&"item");
+        let item = String::from("item");
+        assert!(contains(&bloom, &item));
+
+        // and so can every other filter.
+        macro_rules! assert_covariant {
+            ($($filter:ident),*) => {$({
+                fn shorten<'a>(filter: $filter<&'static str>) -> $filter<&'a str> {
+                    filter
+                }
+                let _ = shorten;
+            })*};
+        }
+        assert_covariant!(
+            BitSlicedBloomIndex,
+            BlockedBloomFilter,
+            ConcurrentBloomFilter,
+            CountingBloomFilter,
+            CuckooFilter,
+            FilterSet,
+            PartitionedBloomFilter,
+            QuotientFilter,
+            ScalableBloomFilter,
+            SlidingWindowBloomFilter,
+            StableBloomFilter,
+            XorFilter8,
+            BinaryFuseFilter8
+        );
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let mut bloom: BloomFilter<i32> = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            bloom.insert(&i);
+        }
+
+        let bloom = Arc::new(bloom);
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let bloom = Arc::clone(&bloom);
+                thread::spawn(move || (0..1000).all(|i| bloom.contains(&i)))
+            })
+            .collect();
+
+        for reader in readers {
+            assert!(reader.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn custom_hashers() {
+        use std::collections::hash_map::RandomState;
+
+        let mut bloom: BloomFilter<str, _> = BloomFilter::with_hashers(100, 0.01, RandomState::new(), RandomState::new());
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+        assert!(!bloom.contains("other"));
+    }
 }
@@ -0,0 +1,120 @@
+use super::{BloomError, BloomFilter};
+use std::hash::BuildHasher;
+use std::ops::{BitAnd, BitOr};
+
+impl<T: ?Sized, S: BuildHasher + PartialEq> BloomFilter<T, S> {
+    // add every item of `other` to the set, so that the filter contains
+    // everything either filter contained. `insertions` becomes the sum of
+    // both counts.
+    pub fn union(&mut self, other: &Self) -> Result<(), BloomError> {
+        self.check_compatible(other)?;
+        self.bitmap.or(&other.bitmap);
+        self.insertions = self.insertions.saturating_add(other.insertions);
+        Ok(())
+    }
+
+    // keep only the bits set in both filters, so that the filter contains
+    // the items both filters contained (with a higher false positive rate
+    // than a filter built from those items alone). `insertions` becomes the
+    // smaller of both counts.
+    pub fn intersect(&mut self, other: &Self) -> Result<(), BloomError> {
+        self.check_compatible(other)?;
+        self.bitmap.and(&other.bitmap);
+        self.insertions = self.insertions.min(other.insertions);
+        Ok(())
+    }
+
+    // check that `other` has the same parameters and hashers, i.e. that it
+    // sets the same bits for the same items.
+    pub(crate) fn check_compatible(&self, other: &Self) -> Result<(), BloomError> {
+        if self.optimal_m != other.optimal_m {
+            return Err(BloomError::MismatchedOptimalM(self.optimal_m, other.optimal_m));
+        }
+        if self.optimal_k != other.optimal_k {
+            return Err(BloomError::MismatchedOptimalK(self.optimal_k, other.optimal_k));
+        }
+        if self.hashers != other.hashers {
+            return Err(BloomError::MismatchedHashers);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + PartialEq + Clone> BitOr for &BloomFilter<T, S> {
+    type Output = Result<BloomFilter<T, S>, BloomError>;
+
+    fn bitor(self, other: Self) -> Self::Output {
+        let mut union = self.clone();
+        union.union(other)?;
+        Ok(union)
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + PartialEq + Clone> BitAnd for &BloomFilter<T, S> {
+    type Output = Result<BloomFilter<T, S>, BloomError>;
+
+    fn bitand(self, other: Self) -> Self::Output {
+        let mut intersection = self.clone();
+        intersection.intersect(other)?;
+        Ok(intersection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(items: std::ops::Range<i32>) -> BloomFilter<i32> {
+        let mut bloom = BloomFilter::with_seeds(1000, 0.01, 1, 2);
+        for i in items {
+            bloom.insert(&i);
+        }
+        bloom
+    }
+
+    #[test]
+    fn union() {
+        let mut a = filter(0..100);
+        let b = filter(100..200);
+
+        let merged = (&a | &b).unwrap();
+        assert!((0..200).all(|i| merged.contains(&i)));
+        assert_eq!(merged.insertions(), 200);
+
+        a.union(&b).unwrap();
+        assert_eq!(a.bitmap, merged.bitmap);
+        assert_eq!(a.bitmap, filter(0..200).bitmap);
+    }
+
+    #[test]
+    fn intersect() {
+        let mut a = filter(0..150);
+        let b = filter(50..200);
+
+        let common = (&a & &b).unwrap();
+        assert!((50..150).all(|i| common.contains(&i)));
+        assert_eq!(common.insertions(), 150);
+
+        a.intersect(&b).unwrap();
+        assert_eq!(a.bitmap, common.bitmap);
+    }
+
+    #[test]
+    fn rejects_incompatible_filters() {
+        let a = filter(0..100);
+
+        let b: BloomFilter<i32> = BloomFilter::with_seeds(2000, 0.01, 1, 2);
+        assert_eq!(
+            (&a | &b).err(),
+            Some(BloomError::MismatchedOptimalM(a.optimal_m, b.optimal_m))
+        );
+
+        let b: BloomFilter<i32> = BloomFilter::with_seeds(1000, 0.001, 1, 2);
+        assert!(matches!((&a & &b).err(), Some(BloomError::MismatchedOptimalM(..))));
+
+        let mut b: BloomFilter<i32> = BloomFilter::with_seeds(1000, 0.01, 1, 3);
+        assert_eq!(b.union(&a), Err(BloomError::MismatchedHashers));
+        assert_eq!(b.intersect(&a), Err(BloomError::MismatchedHashers));
+    }
+}
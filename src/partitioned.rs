@@ -0,0 +1,118 @@
+use super::{get_index, hash_kernel, parameters, BloomError, SeededState};
+use bit_vec::BitVec;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// a Bloom filter whose bitmap is split into `optimal_k` equal partitions,
+// with hash function i setting bits only in partition i. every item sets
+// exactly one bit per partition, so no two of its hashes can collide with
+// each other. created for `items_count` items at a false positive rate of
+// `fp_rate`, it is sized and hashes like `BloomFilter`, with `optimal_m`
+// rounded up to a multiple of `optimal_k`.
+pub struct PartitionedBloomFilter<T: ?Sized, S = SeededState> {
+    bitmap: BitVec,
+    partition_len: usize,
+    optimal_k: u32,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(PartitionedBloomFilter(items_count: usize, fp_rate: f64));
+
+impl<T: ?Sized, S: BuildHasher> PartitionedBloomFilter<T, S> {
+    // create a new PartitionedBloomFilter like `new`, deriving its two hash
+    // functions from `hasher1` and `hasher2`, returning an error instead of
+    // panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        let (optimal_m, optimal_k) = parameters(items_count, fp_rate)?;
+        let partition_len = optimal_m.div_ceil(optimal_k as usize);
+
+        Ok(PartitionedBloomFilter {
+            bitmap: BitVec::from_elem(partition_len * optimal_k as usize, false),
+            partition_len,
+            optimal_k,
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of partitions, one per hash function.
+    pub fn partition_count(&self) -> u32 {
+        self.optimal_k
+    }
+
+    // get the number of bits in each partition.
+    pub fn partition_len(&self) -> usize {
+        self.partition_len
+    }
+
+    // insert items into the set.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+
+        for k_i in 0..self.optimal_k {
+            let index = self.get_index(h1, h2, k_i);
+            self.bitmap.set(index, true);
+        }
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+
+        (0..self.optimal_k).all(|k_i| self.bitmap[self.get_index(h1, h2, k_i)])
+    }
+
+    // get the index of the bit that hash `k_i` sets in partition `k_i`.
+    fn get_index(&self, h1: u64, h2: u64, k_i: u32) -> usize {
+        k_i as usize * self.partition_len + get_index(h1, h2, k_i as u64, self.partition_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let mut bloom = PartitionedBloomFilter::new(100, 0.01);
+        assert!(!bloom.contains("item"));
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+    }
+
+    #[test]
+    fn one_bit_per_partition() {
+        let mut bloom = PartitionedBloomFilter::with_seeds(1000, 0.01, 1, 2);
+        let (optimal_m, optimal_k) = parameters(1000, 0.01).unwrap();
+        assert_eq!(bloom.partition_count(), optimal_k);
+        assert_eq!(bloom.partition_len(), optimal_m.div_ceil(optimal_k as usize));
+
+        bloom.insert("item");
+        for partition in bloom.bitmap.iter().collect::<Vec<_>>().chunks(bloom.partition_len()) {
+            assert_eq!(partition.iter().filter(|&&bit| bit).count(), 1);
+        }
+    }
+
+    #[test]
+    fn meets_fp_rate() {
+        crate::assert_fp_rate(
+            &mut PartitionedBloomFilter::with_seeds(10_000, 0.01, 1, 2),
+            |bloom, i| bloom.insert(&i),
+            |bloom, i| bloom.contains(&i),
+            0.011,
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use super::BloomFilter;
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+// the two hash values a BloomFilter derives an item's k bit indexes from.
+//
+// a BloomHash can be computed once with `BloomFilter::hash` and probed
+// against every filter built with the same hashers, or made from a hash the
+// caller already holds with `from_u64` or `from_u128`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BloomHash {
+    pub h1: u64,
+    pub h2: u64,
+}
+
+impl BloomHash {
+    // make a BloomHash from a single 64-bit hash, deriving `h2` by mixing
+    // it (with the murmur3 64-bit finalizer), so that the k indexes do not
+    // all fall on a line through the same two values.
+    pub fn from_u64(hash: u64) -> Self {
+        let mut h2 = hash ^ 0x9e37_79b9_7f4a_7c15;
+        h2 ^= h2 >> 33;
+        h2 = h2.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h2 ^= h2 >> 33;
+        h2 = h2.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h2 ^= h2 >> 33;
+
+        BloomHash { h1: hash, h2 }
+    }
+
+    // make a BloomHash from a 128-bit hash: its low half is `h1` and its
+    // high half `h2`.
+    pub fn from_u128(hash: u128) -> Self {
+        BloomHash {
+            h1: hash as u64,
+            h2: (hash >> 64) as u64,
+        }
+    }
+}
+
+impl From<u64> for BloomHash {
+    fn from(hash: u64) -> Self {
+        BloomHash::from_u64(hash)
+    }
+}
+
+impl From<u128> for BloomHash {
+    fn from(hash: u128) -> Self {
+        BloomHash::from_u128(hash)
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> BloomFilter<T, S> {
+    // hash an item the way `insert` and `contains` do.
+    pub fn hash<Q>(&self, item: &Q) -> BloomHash
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let (h1, h2) = self.hash_kernel(item);
+        BloomHash { h1, h2 }
+    }
+
+    // insert the item whose hash is `hash`. like `insert`, this counts as
+    // one insertion.
+    pub fn insert_bloom_hash(&mut self, hash: BloomHash) {
+        self.set_bits(hash.h1, hash.h2);
+    }
+
+    // check if the item whose hash is `hash` is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains_bloom_hash(&self, hash: BloomHash) -> bool {
+        self.has_bits(hash.h1, hash.h2)
+    }
+
+    // insert an item by a 64-bit hash the caller computed, skipping the
+    // filter's hashers; see `BloomHash::from_u64`.
+    pub fn insert_hash(&mut self, hash: u64) {
+        self.insert_bloom_hash(BloomHash::from_u64(hash));
+    }
+
+    // check if an item is present in the set by the 64-bit hash it was
+    // inserted with.
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        self.contains_bloom_hash(BloomHash::from_u64(hash))
+    }
+
+    // insert an item by a 128-bit hash the caller computed, skipping the
+    // filter's hashers; see `BloomHash::from_u128`.
+    pub fn insert_hash128(&mut self, hash: u128) {
+        self.insert_bloom_hash(BloomHash::from_u128(hash));
+    }
+
+    // check if an item is present in the set by the 128-bit hash it was
+    // inserted with.
+    pub fn contains_hash128(&self, hash: u128) -> bool {
+        self.contains_bloom_hash(BloomHash::from_u128(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_matches_insert_and_contains() {
+        let mut bloom: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        let hash = bloom.hash("item");
+        assert_eq!((hash.h1, hash.h2), bloom.hash_kernel("item"));
+
+        bloom.insert_bloom_hash(hash);
+        assert!(bloom.contains("item"));
+        bloom.insert("other");
+        assert!(bloom.contains_bloom_hash(bloom.hash("other")));
+        assert_eq!(bloom.insertions(), 2);
+    }
+
+    #[test]
+    fn probe_many_filters() {
+        let mut filters: Vec<BloomFilter<i32>> = (0..4)
+            .map(|_| BloomFilter::with_seeds(1000, 0.01, 1, 2))
+            .collect();
+        filters[2].insert(&42);
+
+        let hash = filters[0].hash(&42);
+        let hits: Vec<bool> = filters
+            .iter()
+            .map(|bloom| bloom.contains_bloom_hash(hash))
+            .collect();
+        assert_eq!(hits, [false, false, true, false]);
+    }
+
+    #[test]
+    fn caller_hashes() {
+        let hash64 = |i: u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let hash128 = |i: u64| ((i as u128) << 64) | (i as u128 * 3);
+
+        let mut bloom: BloomFilter<u64> = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            bloom.insert_hash(hash64(i));
+            bloom.insert_hash128(hash128(i));
+        }
+
+        assert!((0..1000).all(|i| bloom.contains_hash(hash64(i))));
+        assert!((0..1000).all(|i| bloom.contains_hash128(hash128(i))));
+
+        assert_eq!(
+            BloomHash::from_u128(1 << 64 | 2),
+            BloomHash { h1: 2, h2: 1 }
+        );
+        assert_eq!(BloomHash::from(7u64).h1, 7);
+        assert_ne!(BloomHash::from(7u64).h2, 7);
+    }
+}
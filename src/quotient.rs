@@ -0,0 +1,673 @@
+use super::{validate, BloomError, SeededState};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// the load `new` sizes the table for; clusters stay short below it.
+const DEFAULT_LOAD_FACTOR: f64 = 0.75;
+// the load past which inserts fail.
+const MAX_LOAD_FACTOR: f64 = 0.95;
+// remainders are stored alongside three metadata bits in at most 64 bits.
+const MAX_REMAINDER_BITS: u32 = 61;
+
+// the metadata bits of a slot, below its remainder.
+// a run of the slot's own quotient is stored somewhere in the table.
+const OCCUPIED: u64 = 1;
+// the slot continues the run of the slot before it.
+const CONTINUATION: u64 = 2;
+// the remainder in the slot is not in its canonical slot.
+const SHIFTED: u64 = 4;
+const METADATA: u64 = OCCUPIED | CONTINUATION | SHIFTED;
+const METADATA_BITS: u32 = 3;
+
+// a quotient filter (Bender et al., "Don't Thrash: How to Cache Your Hash
+// on Flash", 2012). it splits a fingerprint of each item into a quotient,
+// which picks a slot, and a remainder stored in the table; remainders of the
+// same quotient are kept together in a run, shifted right of their slot when
+// it is taken.
+//
+// because the full fingerprint of every item can be recovered from the
+// table, a quotient filter can be resized and merged without the original
+// items, and items can be removed. removing an item that was never inserted
+// can remove the fingerprint of another item; only remove items known to be
+// in the set.
+pub struct QuotientFilter<T: ?Sized, S = SeededState> {
+    // the slots, `remainder_bits + 3` bits each, packed into words.
+    slots: Vec<u64>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+    hasher: S,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> QuotientFilter<T> {
+    // create a new QuotientFilter that expects to store `items_count`
+    // membership with a false positive rate of the value specified in
+    // `fp_rate`. the hash function is keyed by a random seed.
+    //
+    // panics on the same invalid parameters as `BloomFilter::new`, and if
+    // `fp_rate` is too small to reach with 61-bit remainders.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        Self::with_hasher(items_count, fp_rate, SeededState::new())
+    }
+
+    // create a new QuotientFilter like `new`, returning an error instead of
+    // panicking on invalid parameters.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        Self::try_with_hasher(items_count, fp_rate, SeededState::new())
+    }
+
+    // create a new QuotientFilter whose hash function is keyed by `seed`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        Self::with_hasher(items_count, fp_rate, SeededState::with_seed(seed))
+    }
+
+    // create a new QuotientFilter like `with_seed`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        Self::try_with_hasher(items_count, fp_rate, SeededState::with_seed(seed))
+    }
+
+    // get the seed the hash function is keyed by.
+    pub fn seed(&self) -> u64 {
+        self.hasher.seed()
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> QuotientFilter<T, S> {
+    // create a new QuotientFilter like `new`, hashing items with `hasher`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_hasher(items_count: usize, fp_rate: f64, hasher: S) -> Self {
+        Self::try_with_hasher(items_count, fp_rate, hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new QuotientFilter like `with_hasher`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_hasher(
+        items_count: usize,
+        fp_rate: f64,
+        hasher: S,
+    ) -> Result<Self, BloomError> {
+        validate(items_count, fp_rate)?;
+
+        // a lookup compares the item's remainder against those stored for
+        // its quotient, fewer than one on average, each matching with
+        // probability 2^-r.
+        let remainder_bits = ((1.0 / fp_rate).log2().ceil() as u32).max(1);
+        if remainder_bits > MAX_REMAINDER_BITS {
+            return Err(BloomError::FpRateOutOfRange(fp_rate));
+        }
+
+        let slots = (items_count as f64 / DEFAULT_LOAD_FACTOR).ceil();
+        let quotient_bits = (slots as usize)
+            .max(2)
+            .checked_next_power_of_two()
+            .filter(|_| slots < usize::MAX as f64)
+            .ok_or(BloomError::BitmapTooLarge)?
+            .trailing_zeros();
+
+        let mut filter = QuotientFilter {
+            slots: Vec::new(),
+            quotient_bits: 0,
+            remainder_bits: 0,
+            len: 0,
+            hasher,
+            _marker: PhantomData,
+        };
+        filter.refill(quotient_bits, remainder_bits, Vec::new())?;
+        Ok(filter)
+    }
+
+    // get the hash builder items are hashed with.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    // get the number of items in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // check if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // get the number of items the filter has room for before inserts fail.
+    pub fn capacity(&self) -> usize {
+        capacity(self.quotient_bits)
+    }
+
+    // get the number of fingerprint bits that pick the slot. the table has
+    // 2^quotient_bits slots.
+    pub fn quotient_bits(&self) -> u32 {
+        self.quotient_bits
+    }
+
+    // get the number of fingerprint bits stored in the slot.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    // get the size of the fingerprints, which stays the same across
+    // `resize` and `merge`.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits + self.remainder_bits
+    }
+
+    // insert items into the set. inserting an item twice stores it twice,
+    // so it must be removed twice.
+    //
+    // returns `BloomError::FilterFull`, leaving the filter unchanged, when
+    // the filter is at capacity; `resize` makes room.
+    pub fn insert(&mut self, item: &T) -> Result<(), BloomError>
+    where
+        T: Hash,
+    {
+        if self.len >= self.capacity() {
+            return Err(BloomError::FilterFull);
+        }
+
+        let (quotient, remainder) = self.locate(item);
+        self.insert_fingerprint(quotient, remainder);
+        self.len += 1;
+        Ok(())
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (quotient, remainder) = self.locate(item);
+        self.find(quotient, remainder).is_some()
+    }
+
+    // remove one occurrence of an item from the set, returning whether the
+    // item was present.
+    pub fn remove(&mut self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let (quotient, remainder) = self.locate(item);
+        let Some((run_start, index)) = self.find(quotient, remainder) else {
+            return false;
+        };
+
+        let single = index == run_start && self.get(self.next(index)) & CONTINUATION == 0;
+        if single {
+            let slot = self.get(quotient);
+            self.set(quotient, slot & !OCCUPIED);
+        }
+
+        // shift the rest of the cluster left into the freed slot, up to the
+        // first remainder that is back in its canonical slot.
+        let mut hole = index;
+        let mut current = quotient;
+        let mut promote = index == run_start;
+        let mut i = self.next(index);
+
+        loop {
+            let slot = self.get(i);
+            if slot & (CONTINUATION | SHIFTED) == 0 {
+                break;
+            }
+
+            let mut moved = slot & !OCCUPIED;
+            if slot & CONTINUATION == 0 {
+                current = self.next_occupied(current);
+            } else if promote {
+                // the run lost its first remainder; the next one starts it.
+                moved &= !CONTINUATION;
+            }
+            promote = false;
+
+            moved = if hole == current {
+                moved & !SHIFTED
+            } else {
+                moved | SHIFTED
+            };
+            let kept = self.get(hole) & OCCUPIED;
+            self.set(hole, moved | kept);
+
+            hole = i;
+            i = self.next(i);
+        }
+
+        let kept = self.get(hole) & OCCUPIED;
+        self.set(hole, kept);
+        self.len -= 1;
+        true
+    }
+
+    // double the number of slots, moving one bit of every fingerprint from
+    // its remainder to its quotient. this doubles the false positive rate of
+    // a full filter.
+    //
+    // returns `BloomError::InvalidParameter` if the remainders are down to a
+    // single bit.
+    pub fn resize(&mut self) -> Result<(), BloomError> {
+        if self.remainder_bits == 1 {
+            return Err(BloomError::InvalidParameter(
+                "remainders are down to a single bit",
+            ));
+        }
+
+        let fingerprints = self.fingerprints();
+        self.refill(
+            self.quotient_bits + 1,
+            self.remainder_bits - 1,
+            fingerprints,
+        )
+    }
+
+    // add every item of `other` to the set, resizing the filter if it is too
+    // small to hold both.
+    //
+    // the filters must hash items the same way and keep fingerprints of the
+    // same size, though they may have been resized a different number of
+    // times.
+    pub fn merge(&mut self, other: &Self) -> Result<(), BloomError>
+    where
+        S: PartialEq,
+    {
+        if self.hasher != other.hasher {
+            return Err(BloomError::MismatchedHashers);
+        }
+        if self.fingerprint_bits() != other.fingerprint_bits() {
+            return Err(BloomError::MismatchedFingerprintBits(
+                self.fingerprint_bits(),
+                other.fingerprint_bits(),
+            ));
+        }
+
+        let len = self.len + other.len;
+        let mut quotient_bits = self.quotient_bits.max(other.quotient_bits);
+        while capacity(quotient_bits) < len {
+            // keep at least one bit of every fingerprint in its remainder.
+            if quotient_bits + 1 >= self.fingerprint_bits() {
+                return Err(BloomError::FilterFull);
+            }
+            quotient_bits += 1;
+        }
+
+        let mut fingerprints = self.fingerprints();
+        fingerprints.extend(other.fingerprints());
+        let remainder_bits = self.fingerprint_bits() - quotient_bits;
+        self.refill(quotient_bits, remainder_bits, fingerprints)
+    }
+
+    // split the fingerprint of `item` into its quotient and remainder.
+    fn locate(&self, item: &T) -> (usize, u64)
+    where
+        T: Hash,
+    {
+        let fingerprint = low_bits(self.hasher.hash_one(item), self.fingerprint_bits());
+        self.split(fingerprint)
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let quotient = (fingerprint >> self.remainder_bits) as usize;
+        (quotient, low_bits(fingerprint, self.remainder_bits))
+    }
+
+    // find `remainder` in the run of `quotient`, returning the start of the
+    // run and the slot holding the remainder.
+    fn find(&self, quotient: usize, remainder: u64) -> Option<(usize, usize)> {
+        if self.get(quotient) & OCCUPIED == 0 {
+            return None;
+        }
+
+        let run_start = self.run_start(quotient);
+        let mut i = run_start;
+        loop {
+            if self.get(i) >> METADATA_BITS == remainder {
+                return Some((run_start, i));
+            }
+            i = self.next(i);
+            if self.get(i) & CONTINUATION == 0 {
+                return None;
+            }
+        }
+    }
+
+    // find where the run of `quotient` starts, whose occupied bit must be
+    // set: walk back to the start of the cluster, then forward one run per
+    // occupied slot until reaching `quotient`.
+    fn run_start(&self, quotient: usize) -> usize {
+        let mut b = quotient;
+        while self.get(b) & SHIFTED != 0 {
+            b = self.prev(b);
+        }
+
+        let mut s = b;
+        while b != quotient {
+            loop {
+                s = self.next(s);
+                if self.get(s) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            b = self.next_occupied(b);
+        }
+
+        s
+    }
+
+    // store a fingerprint, without checking the load of the table.
+    fn insert_fingerprint(&mut self, quotient: usize, remainder: u64) {
+        let slot = self.get(quotient);
+        if slot & METADATA == 0 {
+            self.set(quotient, remainder << METADATA_BITS | OCCUPIED);
+            return;
+        }
+
+        self.set(quotient, slot | OCCUPIED);
+        let mut index = self.run_start(quotient);
+        let mut entry = remainder << METADATA_BITS;
+
+        // append to the existing run, or start a new one where it belongs.
+        if slot & OCCUPIED != 0 {
+            loop {
+                index = self.next(index);
+                if self.get(index) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            entry |= CONTINUATION;
+        }
+        if index != quotient {
+            entry |= SHIFTED;
+        }
+
+        // push the rest of the cluster right, up to the first empty slot.
+        // occupied bits belong to the slot, not the remainder, so they stay.
+        loop {
+            let slot = self.get(index);
+            self.set(index, entry | slot & OCCUPIED);
+            if slot & METADATA == 0 {
+                break;
+            }
+
+            entry = slot & !OCCUPIED | SHIFTED;
+            index = self.next(index);
+        }
+    }
+
+    // get the fingerprints of all items, in no particular order.
+    fn fingerprints(&self) -> Vec<u64> {
+        let mut fingerprints = Vec::with_capacity(self.len);
+        if self.len == 0 {
+            return fingerprints;
+        }
+
+        // start just past an empty slot, so the walk starts at a cluster.
+        let slot_count = self.slot_count();
+        let empty = (0..slot_count)
+            .find(|&i| self.get(i) & METADATA == 0)
+            .unwrap_or(0);
+        let mut quotient = empty;
+        let mut i = empty;
+
+        for _ in 0..slot_count {
+            i = self.next(i);
+            let slot = self.get(i);
+            if slot & METADATA == 0 {
+                continue;
+            }
+
+            if slot & SHIFTED == 0 {
+                quotient = i;
+            } else if slot & CONTINUATION == 0 {
+                quotient = self.next_occupied(quotient);
+            }
+            fingerprints.push((quotient as u64) << self.remainder_bits | slot >> METADATA_BITS);
+        }
+
+        fingerprints
+    }
+
+    // replace the table with an empty one of 2^quotient_bits slots holding
+    // remainders of `remainder_bits`, and insert `fingerprints` into it.
+    fn refill(
+        &mut self,
+        quotient_bits: u32,
+        remainder_bits: u32,
+        fingerprints: Vec<u64>,
+    ) -> Result<(), BloomError> {
+        // fingerprints are cut from a 64-bit hash.
+        if quotient_bits >= usize::BITS || quotient_bits + remainder_bits > 64 {
+            return Err(BloomError::BitmapTooLarge);
+        }
+
+        let bits = (1usize << quotient_bits)
+            .checked_mul((remainder_bits + METADATA_BITS) as usize)
+            .ok_or(BloomError::BitmapTooLarge)?;
+
+        self.slots = vec![0; bits.div_ceil(64)];
+        self.quotient_bits = quotient_bits;
+        self.remainder_bits = remainder_bits;
+        self.len = fingerprints.len();
+
+        for fingerprint in fingerprints {
+            let (quotient, remainder) = self.split(fingerprint);
+            self.insert_fingerprint(quotient, remainder);
+        }
+
+        Ok(())
+    }
+
+    fn slot_count(&self) -> usize {
+        1 << self.quotient_bits
+    }
+
+    fn next(&self, i: usize) -> usize {
+        (i + 1) & (self.slot_count() - 1)
+    }
+
+    fn prev(&self, i: usize) -> usize {
+        i.wrapping_sub(1) & (self.slot_count() - 1)
+    }
+
+    // get the next slot after `i` whose occupied bit is set.
+    fn next_occupied(&self, mut i: usize) -> usize {
+        loop {
+            i = self.next(i);
+            if self.get(i) & OCCUPIED != 0 {
+                return i;
+            }
+        }
+    }
+
+    fn slot_bits(&self) -> u32 {
+        self.remainder_bits + METADATA_BITS
+    }
+
+    // get the slot at `index`.
+    fn get(&self, index: usize) -> u64 {
+        let width = self.slot_bits() as usize;
+        let bit = index * width;
+        let (word, offset) = (bit / 64, bit % 64);
+
+        let mut value = self.slots[word] >> offset;
+        if offset + width > 64 {
+            value |= self.slots[word + 1] << (64 - offset);
+        }
+
+        low_bits(value, self.slot_bits())
+    }
+
+    // set the slot at `index`.
+    fn set(&mut self, index: usize, value: u64) {
+        let width = self.slot_bits() as usize;
+        let bit = index * width;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mask = low_bits(u64::MAX, self.slot_bits());
+
+        self.slots[word] = (self.slots[word] & !(mask << offset)) | value << offset;
+
+        if offset + width > 64 {
+            let shift = 64 - offset;
+            self.slots[word + 1] = (self.slots[word + 1] & !(mask >> shift)) | value >> shift;
+        }
+    }
+}
+
+// get the number of items a table of 2^quotient_bits slots holds before
+// inserts fail. at least one slot is always left empty.
+fn capacity(quotient_bits: u32) -> usize {
+    let Some(slot_count) = 1usize.checked_shl(quotient_bits) else {
+        return usize::MAX;
+    };
+    ((slot_count as f64 * MAX_LOAD_FACTOR) as usize).min(slot_count - 1)
+}
+
+fn low_bits(value: u64, bits: u32) -> u64 {
+    if bits >= 64 {
+        value
+    } else {
+        value & ((1 << bits) - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut quotient = QuotientFilter::new(100, 0.01);
+        assert!(!quotient.contains("item"));
+
+        quotient.insert("item").unwrap();
+        quotient.insert("item").unwrap();
+        assert!(quotient.contains("item"));
+        assert_eq!(quotient.len(), 2);
+
+        assert!(quotient.remove("item"));
+        assert!(quotient.contains("item"));
+        assert!(quotient.remove("item"));
+        assert!(!quotient.contains("item"));
+        assert!(!quotient.remove("item"));
+        assert!(quotient.is_empty());
+    }
+
+    #[test]
+    fn meets_fp_rate() {
+        crate::assert_fp_rate(
+            &mut QuotientFilter::with_seed(10_000, 0.01, 1),
+            |quotient, i| quotient.insert(&i).unwrap(),
+            |quotient, i| quotient.contains(&i),
+            0.01,
+        );
+    }
+
+    #[test]
+    fn removes_keep_other_items() {
+        // a small table with short remainders, so runs and clusters are long
+        // and many fingerprints collide.
+        let mut quotient = QuotientFilter::with_seed(2000, 0.2, 7);
+        for i in 0..quotient.capacity() as u64 {
+            quotient.insert(&i).unwrap();
+        }
+        let inserted = quotient.len() as u64;
+
+        for i in (0..inserted).step_by(2) {
+            assert!(quotient.remove(&i));
+        }
+
+        assert_eq!(quotient.len() as u64, inserted / 2);
+        assert_eq!(quotient.fingerprints().len(), quotient.len());
+        assert!((1..inserted).step_by(2).all(|i| quotient.contains(&i)));
+
+        for i in (1..inserted).step_by(2) {
+            assert!(quotient.remove(&i));
+        }
+        assert!(quotient.slots.iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn fills_up() {
+        let mut quotient = QuotientFilter::new(100, 0.01);
+        let mut inserted = 0;
+        while quotient.insert(&inserted).is_ok() {
+            inserted += 1;
+        }
+
+        assert_eq!(inserted, quotient.capacity());
+        assert_eq!(quotient.insert(&inserted), Err(BloomError::FilterFull));
+        assert!((0..inserted).all(|i| quotient.contains(&i)));
+    }
+
+    #[test]
+    fn resize() {
+        let mut quotient = QuotientFilter::with_seed(100, 0.01, 1);
+        let (quotient_bits, remainder_bits) = (quotient.quotient_bits(), quotient.remainder_bits());
+        for i in 0..quotient.capacity() {
+            quotient.insert(&i).unwrap();
+        }
+
+        quotient.resize().unwrap();
+        assert_eq!(quotient.quotient_bits(), quotient_bits + 1);
+        assert_eq!(quotient.remainder_bits(), remainder_bits - 1);
+        assert!((0..quotient.len()).all(|i| quotient.contains(&i)));
+
+        let len = quotient.len();
+        quotient.insert(&len).unwrap();
+        assert!(quotient.contains(&len));
+
+        while quotient.remainder_bits() > 1 {
+            quotient.resize().unwrap();
+        }
+        assert_eq!(
+            quotient.resize(),
+            Err(BloomError::InvalidParameter(
+                "remainders are down to a single bit"
+            ))
+        );
+        assert!((0..=len).all(|i| quotient.contains(&i)));
+    }
+
+    #[test]
+    fn merge() {
+        // both keep 18-bit fingerprints: 11 + 7 and, after resizing, 9 + 9.
+        let mut left = QuotientFilter::with_seed(1000, 0.01, 1);
+        let mut right = QuotientFilter::with_seed(100, 0.001, 1);
+        right.resize().unwrap();
+        for i in 0..700 {
+            left.insert(&i).unwrap();
+        }
+        for i in 500..600 {
+            right.insert(&i).unwrap();
+        }
+
+        left.merge(&right).unwrap();
+        assert_eq!(left.len(), 800);
+        assert_eq!(left.quotient_bits(), 11);
+        assert!((0..700).all(|i| left.contains(&i)));
+
+        // 300 items do not fit in 256 slots, so the merge resizes.
+        let mut low = QuotientFilter::with_seed(150, 0.01, 1);
+        let mut high = QuotientFilter::with_seed(150, 0.01, 1);
+        assert_eq!(low.quotient_bits(), 8);
+        for i in 0..150 {
+            low.insert(&i).unwrap();
+            high.insert(&(i + 150)).unwrap();
+        }
+        low.merge(&high).unwrap();
+        assert_eq!(low.quotient_bits(), 9);
+        assert!((0..300).all(|i| low.contains(&i)));
+
+        let other = QuotientFilter::with_seed(1000, 0.01, 2);
+        assert_eq!(left.merge(&other), Err(BloomError::MismatchedHashers));
+        let other = QuotientFilter::with_seed(1000, 0.001, 1);
+        assert_eq!(
+            left.merge(&other),
+            Err(BloomError::MismatchedFingerprintBits(18, 21))
+        );
+    }
+}
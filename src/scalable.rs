@@ -0,0 +1,177 @@
+use super::{validate, BloomError, BloomFilter, SeededState};
+use std::hash::{BuildHasher, Hash};
+
+// each slice expects this many times the items of the slice before it.
+const GROWTH_FACTOR: usize = 2;
+// each slice has this fraction of the false positive rate of the slice before it.
+const TIGHTENING_RATIO: f64 = 0.9;
+
+// a Bloom filter that grows past the number of items it was created for
+// (Almeida et al., "Scalable Bloom Filters", 2007).
+//
+// items go into a chain of BloomFilter slices, the first expecting
+// `items_count` items. when the newest slice is full, a slice expecting
+// `GROWTH_FACTOR` times as many items is added, with `TIGHTENING_RATIO`
+// times the false positive rate. the slice rates form a geometric series,
+// so the compound false positive rate stays below `fp_rate` however many
+// items are inserted. `items_count` and `fp_rate` are checked like those of
+// `BloomFilter`.
+pub struct ScalableBloomFilter<T: ?Sized, S = SeededState> {
+    slices: Vec<BloomFilter<T, S>>,
+    // the number of items the newest slice expects and holds.
+    slice_capacity: usize,
+    slice_len: usize,
+    fp_rate: f64,
+    len: usize,
+}
+
+seeded_constructors!(
+    ScalableBloomFilter(items_count: usize, fp_rate: f64) where S: Clone,
+    seeds: |scalable| scalable.slices[0].seeds()
+);
+
+impl<T: ?Sized, S: BuildHasher + Clone> ScalableBloomFilter<T, S> {
+    // create a new ScalableBloomFilter like `new`, deriving the two hash
+    // functions of every slice from `hasher1` and `hasher2`, returning an
+    // error instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        validate(items_count, fp_rate)?;
+        let first = BloomFilter::try_with_hashers(
+            items_count,
+            fp_rate * (1.0 - TIGHTENING_RATIO),
+            hasher1,
+            hasher2,
+        )?;
+
+        Ok(ScalableBloomFilter {
+            slices: vec![first],
+            slice_capacity: items_count,
+            slice_len: 0,
+            fp_rate,
+            len: 0,
+        })
+    }
+
+    // get the number of distinct items inserted. items that the filter
+    // already (possibly falsely) contained are not counted.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // check if no items have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // get the number of BloomFilter slices.
+    pub fn slice_count(&self) -> usize {
+        self.slices.len()
+    }
+
+    // get the bound on the false positive rate passed to `new`.
+    pub fn fp_rate(&self) -> f64 {
+        self.fp_rate
+    }
+
+    // get the false positive rate of the slices allocated so far, assuming
+    // each slice is filled to capacity. it is always below `fp_rate`.
+    pub fn compound_fp_rate(&self) -> f64 {
+        let p0 = self.fp_rate * (1.0 - TIGHTENING_RATIO);
+        let mut p_none = 1.0;
+        let mut p_i = p0;
+
+        for _ in &self.slices {
+            p_none *= 1.0 - p_i;
+            p_i *= TIGHTENING_RATIO;
+        }
+
+        1.0 - p_none
+    }
+
+    // insert items into the set, adding a slice when the newest one is full.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        if self.contains(item) {
+            return;
+        }
+
+        if self.slice_len >= self.slice_capacity {
+            self.grow();
+        }
+
+        self.slices.last_mut().unwrap().insert(item);
+        self.slice_len += 1;
+        self.len += 1;
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.slices.iter().rev().any(|slice| slice.contains(item))
+    }
+
+    // add a slice expecting `GROWTH_FACTOR` times the items of the newest
+    // slice, with `TIGHTENING_RATIO` times its false positive rate.
+    fn grow(&mut self) {
+        let newest = self.slices.last().unwrap();
+        let exponent = self.slices.len() as i32;
+        let fp_rate = self.fp_rate * (1.0 - TIGHTENING_RATIO) * TIGHTENING_RATIO.powi(exponent);
+        let capacity = self.slice_capacity.saturating_mul(GROWTH_FACTOR);
+        let (hasher1, hasher2) = newest.hashers();
+
+        let slice = BloomFilter::with_hashers(capacity, fp_rate, hasher1.clone(), hasher2.clone());
+        self.slices.push(slice);
+        self.slice_capacity = capacity;
+        self.slice_len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_past_capacity() {
+        let mut bloom = ScalableBloomFilter::new(100, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(&i);
+        }
+
+        assert!(bloom.slice_count() > 1);
+        assert!(bloom.len() <= 10_000);
+        assert!((0..10_000).all(|i| bloom.contains(&i)));
+        assert!(bloom.compound_fp_rate() < bloom.fp_rate());
+    }
+
+    #[test]
+    fn fp_rate_stays_bounded() {
+        let mut bloom = ScalableBloomFilter::with_seeds(100, 0.01, 1, 2);
+        for i in 0..20_000 {
+            bloom.insert(&i);
+        }
+
+        let false_positives = (20_000..120_000).filter(|i| bloom.contains(i)).count();
+        assert!((false_positives as f64 / 100_000.0) < 0.01);
+    }
+
+    #[test]
+    fn duplicates_do_not_use_capacity() {
+        let mut bloom = ScalableBloomFilter::new(10, 0.01);
+        for _ in 0..100 {
+            bloom.insert("item");
+        }
+
+        assert_eq!(bloom.len(), 1);
+        assert_eq!(bloom.slice_count(), 1);
+    }
+}
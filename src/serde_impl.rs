@@ -0,0 +1,175 @@
+use super::{BloomFilter, MAX_OPTIMAL_K};
+use bit_vec::BitVec;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::marker::PhantomData;
+
+// the serialized form of a BloomFilter: the bitmap packed into bytes
+// (most significant bit first), the filter parameters, the design false
+// positive rate, the insertion count and the hash builders.
+//
+// filters serialized before fp_rate and insertions were added are still
+// read, taking fp_rate to be 2^-optimal_k as the version 1 wire format
+// does, and insertions to be zero.
+#[derive(serde::Deserialize)]
+#[serde(rename = "BloomFilter")]
+struct Repr<S> {
+    bitmap: Vec<u8>,
+    optimal_m: usize,
+    optimal_k: u32,
+    #[serde(default)]
+    fp_rate: Option<f64>,
+    #[serde(default)]
+    insertions: u64,
+    hashers: [S; 2],
+}
+
+impl<T: ?Sized, S: Serialize> Serialize for BloomFilter<T, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut state = serializer.serialize_struct("BloomFilter", 6)?;
+        state.serialize_field("bitmap", &self.bitmap.to_bytes())?;
+        state.serialize_field("optimal_m", &self.optimal_m)?;
+        state.serialize_field("optimal_k", &self.optimal_k)?;
+        state.serialize_field("fp_rate", &self.fp_rate)?;
+        state.serialize_field("insertions", &self.insertions)?;
+        state.serialize_field("hashers", &self.hashers)?;
+        state.end()
+    }
+}
+
+impl<'de, T: ?Sized, S: Deserialize<'de>> Deserialize<'de> for BloomFilter<T, S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = Repr::<S>::deserialize(deserializer)?;
+
+        if repr.optimal_m == 0 {
+            return Err(de::Error::custom("optimal_m must be greater than zero"));
+        }
+        if repr.optimal_k == 0 || repr.optimal_k > MAX_OPTIMAL_K {
+            return Err(de::Error::custom("optimal_k is out of range"));
+        }
+        let fp_rate = repr
+            .fp_rate
+            .unwrap_or_else(|| 0.5f64.powi(repr.optimal_k as i32));
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            return Err(de::Error::custom("fp_rate must be greater than 0 and less than 1"));
+        }
+        if repr.bitmap.len() != repr.optimal_m.div_ceil(8) {
+            return Err(de::Error::invalid_length(
+                repr.bitmap.len(),
+                &"a bitmap of optimal_m bits",
+            ));
+        }
+
+        let mut bitmap = BitVec::from_bytes(&repr.bitmap);
+        bitmap.truncate(repr.optimal_m);
+
+        Ok(BloomFilter {
+            bitmap,
+            optimal_m: repr.optimal_m,
+            optimal_k: repr.optimal_k,
+            fp_rate,
+            insertions: repr.insertions,
+            hashers: repr.hashers,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededState;
+
+    #[test]
+    fn round_trip() {
+        let mut bloom: BloomFilter<i32> = BloomFilter::with_seeds(1000, 0.01, 7, 11);
+        for i in 0..500 {
+            bloom.insert(&i);
+        }
+
+        let json = serde_json::to_string(&bloom).unwrap();
+        let restored: BloomFilter<i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.seeds(), (7, 11));
+        assert_eq!(restored.optimal_m, bloom.optimal_m);
+        assert_eq!(restored.optimal_k, bloom.optimal_k);
+        assert_eq!(restored.bitmap, bloom.bitmap);
+        assert_eq!(restored.fp_rate(), 0.01);
+        assert_eq!(restored.insertions(), 500);
+        for i in 0..2000 {
+            assert_eq!(restored.contains(&i), bloom.contains(&i));
+        }
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let mut bloom: BloomFilter<str> = BloomFilter::new(10, 0.1);
+        let json = serde_json::to_string(&bloom).unwrap();
+        let mut restored: BloomFilter<str> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.seeds(), bloom.seeds());
+        assert_eq!(restored.bitmap, bloom.bitmap);
+        assert!(!restored.contains("item"));
+
+        bloom.insert("item");
+        restored.insert("item");
+        assert_eq!(restored.bitmap, bloom.bitmap);
+    }
+
+    #[test]
+    fn seeded_state_round_trip() {
+        let state = SeededState::with_seed(99);
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<SeededState>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn reads_filters_without_fp_rate_and_insertions() {
+        let mut bloom: BloomFilter<i32> = BloomFilter::with_seeds(1000, 0.01, 7, 11);
+        for i in 0..500 {
+            bloom.insert(&i);
+        }
+
+        let mut value = serde_json::to_value(&bloom).unwrap();
+        let fields = value.as_object_mut().unwrap();
+        fields.remove("fp_rate");
+        fields.remove("insertions");
+        let restored: BloomFilter<i32> = serde_json::from_value(value).unwrap();
+
+        assert_eq!(restored.seeds(), (7, 11));
+        assert_eq!(restored.bitmap, bloom.bitmap);
+        assert_eq!(restored.fp_rate(), 0.5f64.powi(bloom.optimal_k as i32));
+        assert_eq!(restored.insertions(), 0);
+        assert!((0..500).all(|i| restored.contains(&i)));
+    }
+
+    #[test]
+    fn rejects_mismatched_bitmap() {
+        let bloom: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        let mut value = serde_json::to_value(&bloom).unwrap();
+        value["optimal_m"] = serde_json::json!(bloom.optimal_m * 2);
+
+        assert!(serde_json::from_value::<BloomFilter<str>>(value).is_err());
+    }
+
+    #[test]
+    fn rejects_zero_optimal_m() {
+        let bloom: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        let mut value = serde_json::to_value(&bloom).unwrap();
+        value["bitmap"] = serde_json::json!([]);
+        value["optimal_m"] = serde_json::json!(0);
+
+        assert!(serde_json::from_value::<BloomFilter<str>>(value).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_optimal_k() {
+        let bloom: BloomFilter<str> = BloomFilter::with_seeds(100, 0.01, 1, 2);
+        for optimal_k in [0, MAX_OPTIMAL_K + 1] {
+            let mut value = serde_json::to_value(&bloom).unwrap();
+            value["optimal_k"] = serde_json::json!(optimal_k);
+
+            assert!(serde_json::from_value::<BloomFilter<str>>(value).is_err());
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use super::{validate, BloomError, BloomFilter, SeededState};
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+// a source of the current time for a SlidingWindowBloomFilter.
+pub trait Clock {
+    // get the current time. it must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+// the clock of `Instant::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// a Bloom filter that forgets items once they are older than a window of
+// time, answering "was this seen in the last `window`?".
+//
+// items go into a ring of `generations` BloomFilters, each covering a
+// period of window / (generations - 1), rounded up to whole nanoseconds.
+// when the newest generation's period is over, the oldest one is cleared
+// and takes its place. an item is therefore kept for at least `window` and
+// at most `window` plus one period, and `contains` never reports a false
+// negative for an item inserted within the window.
+pub struct SlidingWindowBloomFilter<T: ?Sized, S = SeededState, C = MonotonicClock> {
+    generations: Vec<BloomFilter<T, S>>,
+    // the index of the newest generation, and when its period started.
+    current: usize,
+    current_start: Instant,
+    period: Duration,
+    window: Duration,
+    fp_rate: f64,
+    clock: C,
+}
+
+impl<T: ?Sized> SlidingWindowBloomFilter<T> {
+    // create a new SlidingWindowBloomFilter that expects to see
+    // `items_count` items per `window`, with a false positive rate of the
+    // value specified in `fp_rate`, split into `generations` generations.
+    // the hash functions are keyed by random seeds.
+    //
+    // panics on the same invalid parameters as `BloomFilter::new`, and if
+    // there are fewer than two generations or the window is too short to
+    // split between them.
+    pub fn new(items_count: usize, fp_rate: f64, window: Duration, generations: usize) -> Self {
+        Self::with_clock(items_count, fp_rate, window, generations, MonotonicClock)
+    }
+
+    // create a new SlidingWindowBloomFilter like `new`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_new(
+        items_count: usize,
+        fp_rate: f64,
+        window: Duration,
+        generations: usize,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_clock(items_count, fp_rate, window, generations, MonotonicClock)
+    }
+}
+
+impl<T: ?Sized, C: Clock> SlidingWindowBloomFilter<T, SeededState, C> {
+    // create a new SlidingWindowBloomFilter like `new`, telling time with
+    // `clock`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_clock(
+        items_count: usize,
+        fp_rate: f64,
+        window: Duration,
+        generations: usize,
+        clock: C,
+    ) -> Self {
+        Self::try_with_clock(items_count, fp_rate, window, generations, clock)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new SlidingWindowBloomFilter like `with_clock`, returning an
+    // error instead of panicking on invalid parameters.
+    pub fn try_with_clock(
+        items_count: usize,
+        fp_rate: f64,
+        window: Duration,
+        generations: usize,
+        clock: C,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_hashers(
+            items_count,
+            fp_rate,
+            window,
+            generations,
+            clock,
+            SeededState::new(),
+            SeededState::new(),
+        )
+    }
+
+    // get the seeds the hash functions are keyed by.
+    pub fn seeds(&self) -> (u64, u64) {
+        self.generations[0].seeds()
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + Clone, C: Clock> SlidingWindowBloomFilter<T, S, C> {
+    // create a new SlidingWindowBloomFilter like `with_clock`, deriving the
+    // two hash functions of every generation from `hasher1` and `hasher2`.
+    //
+    // panics on the same invalid parameters as `new`.
+    pub fn with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        window: Duration,
+        generations: usize,
+        clock: C,
+        hasher1: S,
+        hasher2: S,
+    ) -> Self {
+        Self::try_with_hashers(
+            items_count,
+            fp_rate,
+            window,
+            generations,
+            clock,
+            hasher1,
+            hasher2,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new SlidingWindowBloomFilter like `with_hashers`, returning
+    // an error instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        items_count: usize,
+        fp_rate: f64,
+        window: Duration,
+        generations: usize,
+        clock: C,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        validate(items_count, fp_rate)?;
+        if generations < 2 {
+            return Err(BloomError::InvalidParameter(
+                "generations must be at least 2",
+            ));
+        }
+        if generations > u32::MAX as usize {
+            return Err(BloomError::InvalidParameter(
+                "generations must be at most u32::MAX",
+            ));
+        }
+
+        // the period is rounded up to whole nanoseconds, so that the
+        // generations after an item's own cover the whole window.
+        let period = window.as_nanos().div_ceil(generations as u128 - 1);
+        let period = Duration::new(
+            (period / 1_000_000_000) as u64,
+            (period % 1_000_000_000) as u32,
+        );
+        if period.is_zero() {
+            return Err(BloomError::InvalidParameter(
+                "window is too short for the number of generations",
+            ));
+        }
+
+        // a query can match in any generation, so each gets an equal share
+        // of the false positive rate; each holds the items of one period.
+        let generation_items = items_count.div_ceil(generations - 1);
+        let generation_fp_rate = fp_rate / generations as f64;
+        let generations = (0..generations)
+            .map(|_| {
+                BloomFilter::try_with_hashers(
+                    generation_items,
+                    generation_fp_rate,
+                    hasher1.clone(),
+                    hasher2.clone(),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(SlidingWindowBloomFilter {
+            generations,
+            current: 0,
+            current_start: clock.now(),
+            period,
+            window,
+            fp_rate,
+            clock,
+        })
+    }
+
+    // get the window items are remembered for.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    // get the number of generations.
+    pub fn generation_count(&self) -> usize {
+        self.generations.len()
+    }
+
+    // get the period each generation covers.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    // get the false positive rate passed to `new`.
+    pub fn fp_rate(&self) -> f64 {
+        self.fp_rate
+    }
+
+    // get the clock the filter tells time with.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    // insert items into the set, first retiring the generations whose items
+    // have all left the window.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        self.rotate();
+        self.generations[self.current].insert(item);
+    }
+
+    // check if an item was inserted within the window.
+    // false positives are possible, including for items inserted up to one
+    // period before the window, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let elapsed = self
+            .clock
+            .now()
+            .saturating_duration_since(self.current_start);
+        let count = self.generations.len();
+
+        // the generation `age` periods older than the newest stops holding
+        // items inside the window `count - age` periods after the newest
+        // one started; generations are skipped from then on, even before
+        // the next insert clears them.
+        (0..count)
+            .take_while(|&age| {
+                self.period
+                    .checked_mul((count - age) as u32)
+                    .is_none_or(|expiry| elapsed < expiry)
+            })
+            .any(|age| self.generations[(self.current + count - age) % count].contains(item))
+    }
+
+    // start a new generation for every period that has passed since the
+    // newest one started, clearing the oldest.
+    fn rotate(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.current_start);
+        if elapsed < self.period {
+            return;
+        }
+
+        let period = self.period.as_nanos();
+        let passed = elapsed.as_nanos() / period;
+        let count = self.generations.len();
+
+        for _ in 0..passed.min(count as u128) {
+            self.current = (self.current + 1) % count;
+            let generation = &mut self.generations[self.current];
+            generation.bitmap.clear();
+            generation.insertions = 0;
+        }
+
+        let into_period = Duration::from_nanos((elapsed.as_nanos() % period) as u64);
+        self.current_start = now - into_period;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // a clock that only moves when told to.
+    #[derive(Clone)]
+    struct TestClock(Rc<Cell<Instant>>);
+
+    impl TestClock {
+        fn new() -> Self {
+            TestClock(Rc::new(Cell::new(Instant::now())))
+        }
+
+        fn advance(&self, secs: u64) {
+            self.advance_by(Duration::from_secs(secs));
+        }
+
+        fn advance_by(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    fn sliding(clock: &TestClock) -> SlidingWindowBloomFilter<u64, SeededState, TestClock> {
+        SlidingWindowBloomFilter::with_hashers(
+            1000,
+            0.01,
+            Duration::from_secs(60),
+            4,
+            clock.clone(),
+            SeededState::with_seed(1),
+            SeededState::with_seed(2),
+        )
+    }
+
+    #[test]
+    fn insert() {
+        let mut sliding = SlidingWindowBloomFilter::new(100, 0.01, Duration::from_secs(60), 4);
+        assert!(!sliding.contains("item"));
+        sliding.insert("item");
+        assert!(sliding.contains("item"));
+        assert_eq!(sliding.period(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn items_expire() {
+        let clock = TestClock::new();
+        let mut sliding = sliding(&clock);
+        sliding.insert(&1);
+
+        clock.advance(59);
+        assert!(sliding.contains(&1));
+
+        // the generation holding the item expires a period after the window,
+        // whether or not anything is inserted to clear it.
+        clock.advance(21);
+        assert!(!sliding.contains(&1));
+        sliding.insert(&2);
+        assert!(!sliding.contains(&1));
+        assert!(sliding.contains(&2));
+    }
+
+    #[test]
+    fn no_false_negatives_within_window() {
+        let clock = TestClock::new();
+        let mut sliding = sliding(&clock);
+
+        // one item every 3 seconds; everything from the last 60 seconds
+        // must be present, and nothing from more than 80 seconds ago.
+        for i in 0..200u64 {
+            sliding.insert(&i);
+            let recent = i.saturating_sub(19)..=i;
+            assert!(recent.clone().all(|j| sliding.contains(&j)));
+            assert!((0..i.saturating_sub(26)).all(|j| !sliding.contains(&j)));
+            clock.advance(3);
+        }
+    }
+
+    #[test]
+    fn uneven_window() {
+        let clock = TestClock::new();
+        let window = Duration::from_secs(10);
+        let mut sliding = SlidingWindowBloomFilter::<u64, _, _>::with_hashers(
+            1000,
+            0.01,
+            window,
+            7,
+            clock.clone(),
+            SeededState::with_seed(1),
+            SeededState::with_seed(2),
+        );
+        assert_eq!(sliding.period(), Duration::new(1, 666_666_667));
+
+        // inserted at the very end of the first period, the item must
+        // outlive the window measured from then.
+        clock.advance_by(sliding.period() - Duration::from_nanos(1));
+        sliding.insert(&1);
+        clock.advance_by(window - Duration::from_nanos(2));
+        assert!(sliding.contains(&1));
+        sliding.insert(&2);
+        assert!(sliding.contains(&1));
+    }
+
+    #[test]
+    fn long_idle_period() {
+        let clock = TestClock::new();
+        let mut sliding = sliding(&clock);
+        sliding.insert(&1);
+
+        clock.advance(3600);
+        assert!(!sliding.contains(&1));
+        sliding.insert(&2);
+        assert!(sliding.contains(&2));
+        // every generation was cleared, not just the ones due.
+        let insertions: u64 = sliding.generations.iter().map(|g| g.insertions()).sum();
+        assert_eq!(insertions, 1);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let window = Duration::from_secs(60);
+        assert_eq!(
+            SlidingWindowBloomFilter::<u64>::try_new(100, 0.01, window, 1).err(),
+            Some(BloomError::InvalidParameter("generations must be at least 2"))
+        );
+        assert!(SlidingWindowBloomFilter::<u64>::try_new(0, 0.01, window, 4).is_err());
+        assert_eq!(
+            SlidingWindowBloomFilter::<u64>::try_new(100, 0.01, Duration::ZERO, 4).err(),
+            Some(BloomError::InvalidParameter(
+                "window is too short for the number of generations"
+            ))
+        );
+    }
+}
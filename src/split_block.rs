@@ -0,0 +1,306 @@
+// a split block Bloom filter as specified for Apache Parquet column chunks
+// (https://github.com/apache/parquet-format/blob/master/BloomFilter.md).
+//
+// the filter is an array of 256-bit blocks, each made of eight 32-bit words.
+// an item is hashed with xxHash64 (seed 0) of its plain encoding; the upper
+// 32 bits of the hash pick a block and the lower 32 bits set one bit in
+// every word of it. the bitset is stored as the blocks' words in order, each
+// little-endian, exactly as it appears in a Parquet file.
+
+use super::{validate, BloomError};
+use xxhash_rust::xxh64::xxh64;
+
+// the salts the spec multiplies the key by to pick a bit in each word.
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+
+// the number of bytes in a block.
+const BLOCK_BYTES: usize = 32;
+// the bounds Parquet writers place on the size of the bitset.
+const MIN_BITSET_BYTES: usize = BLOCK_BYTES;
+const MAX_BITSET_BYTES: usize = 128 * 1024 * 1024;
+
+type Block = [u32; 8];
+
+// a value that can be added to a SplitBlockBloomFilter, hashed the way
+// Parquet hashes the physical type it is stored as.
+pub trait ParquetValue {
+    // hash the plain encoding of the value with xxHash64.
+    fn parquet_hash(&self) -> u64;
+}
+
+macro_rules! impl_parquet_value_le {
+    ($($ty:ty),*) => {
+        $(
+            impl ParquetValue for $ty {
+                fn parquet_hash(&self) -> u64 {
+                    xxh64(&self.to_le_bytes(), 0)
+                }
+            }
+        )*
+    };
+}
+
+// INT32, INT64, FLOAT and DOUBLE are plain encoded as little-endian bytes;
+// unsigned values are stored in the signed type of the same width.
+impl_parquet_value_le!(i32, i64, u32, u64, f32, f64);
+
+// BYTE_ARRAY and FIXED_LEN_BYTE_ARRAY values are hashed without the length
+// prefix of their plain encoding.
+impl ParquetValue for [u8] {
+    fn parquet_hash(&self) -> u64 {
+        xxh64(self, 0)
+    }
+}
+
+impl ParquetValue for Vec<u8> {
+    fn parquet_hash(&self) -> u64 {
+        self.as_slice().parquet_hash()
+    }
+}
+
+impl ParquetValue for str {
+    fn parquet_hash(&self) -> u64 {
+        self.as_bytes().parquet_hash()
+    }
+}
+
+impl ParquetValue for String {
+    fn parquet_hash(&self) -> u64 {
+        self.as_bytes().parquet_hash()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitBlockBloomFilter {
+    blocks: Vec<Block>,
+}
+
+impl SplitBlockBloomFilter {
+    // create a new SplitBlockBloomFilter that expects to store `items_count`
+    // distinct values with a false positive rate of the value specified in
+    // `fp_rate`, sized the way Parquet writers size it.
+    //
+    // panics on the same invalid parameters as `BloomFilter::new`.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        Self::try_new(items_count, fp_rate).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new SplitBlockBloomFilter like `new`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        validate(items_count, fp_rate)?;
+
+        let bits = -8.0 * items_count as f64 / (1.0 - fp_rate.powf(1.0 / 8.0)).ln();
+        Ok(Self::with_num_bytes((bits / 8.0).min(MAX_BITSET_BYTES as f64) as usize))
+    }
+
+    // create a new empty SplitBlockBloomFilter with a bitset of `num_bytes`,
+    // rounded up to a power of two between 32 bytes and 128 MiB.
+    pub fn with_num_bytes(num_bytes: usize) -> Self {
+        let num_bytes = num_bytes
+            .clamp(MIN_BITSET_BYTES, MAX_BITSET_BYTES)
+            .next_power_of_two();
+
+        SplitBlockBloomFilter { blocks: vec![[0; 8]; num_bytes / BLOCK_BYTES] }
+    }
+
+    // create a SplitBlockBloomFilter from the bitset of a Parquet bloom
+    // filter, which must be a non-empty whole number of blocks.
+    pub fn from_bitset(bitset: &[u8]) -> Result<Self, BloomError> {
+        if bitset.is_empty() || !bitset.len().is_multiple_of(BLOCK_BYTES) {
+            return Err(BloomError::InvalidBitsetLength(bitset.len()));
+        }
+
+        let blocks = bitset
+            .chunks_exact(BLOCK_BYTES)
+            .map(|chunk| {
+                let mut block = [0; 8];
+                for (word, bytes) in block.iter_mut().zip(chunk.chunks_exact(4)) {
+                    *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                block
+            })
+            .collect();
+
+        Ok(SplitBlockBloomFilter { blocks })
+    }
+
+    // get the bitset as it is stored in a Parquet file.
+    pub fn to_bitset(&self) -> Vec<u8> {
+        self.blocks.iter().flatten().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    // get the size of the bitset in bytes.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    // insert a value into the set.
+    pub fn insert<V: ParquetValue + ?Sized>(&mut self, value: &V) {
+        self.insert_hash(value.parquet_hash());
+    }
+
+    // check if a value is present in the set.
+    // false positives are possible, but not false negatives.
+    pub fn contains<V: ParquetValue + ?Sized>(&self, value: &V) -> bool {
+        self.contains_hash(value.parquet_hash())
+    }
+
+    // insert a value by its xxHash64 hash.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let mask = mask(hash as u32);
+
+        for (word, bit) in self.blocks[index].iter_mut().zip(mask) {
+            *word |= bit;
+        }
+    }
+
+    // check if a value is present in the set by its xxHash64 hash.
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        let block = &self.blocks[self.block_index(hash)];
+        block.iter().zip(mask(hash as u32)).all(|(word, bit)| word & bit != 0)
+    }
+
+    // pick the block of `hash` from its upper 32 bits.
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+}
+
+// calculate the bit set in each word of a block for `key`.
+fn mask(key: u32) -> Block {
+    SALT.map(|salt| 1 << (key.wrapping_mul(salt) >> 27))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bitsets of the Parquet file written by the arrow-rs `parquet` crate
+    // (version 53) for the values in `matches_reference_bitsets`, with
+    // bloom filters of 100 distinct values at a 1% false positive rate.
+    const INT64_BITSET: &str = concat!(
+        "0102000000000104003000000000020210000800020000100040400008400000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0011000a800840022000804200400212100400840480000c4000140810010044",
+        "0004000000400000080000000004000000000020000008004000000000000002",
+    );
+    const BYTE_ARRAY_BITSET: &str = concat!(
+        "0400900040024000002440008000088080020004012000801000005002000408",
+        "0080000000000008010000000000000100000020400000000000800000200000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "20000420010006000200001200100810004002022000400100000068000020c0",
+    );
+    const INT32_BITSET: &str = concat!(
+        "0082002000004108085000002200400040200010100060000408010010004020",
+        "4000000000000400000000020040000020000000000000081000000080000000",
+        "0200080080002000020000800240000010002000110000000020400000300000",
+        "2000000000400000080000000000400010000000000010000000400000400000",
+    );
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn insert() {
+        let mut bloom = SplitBlockBloomFilter::new(1000, 0.01);
+        for i in 0..1000i64 {
+            bloom.insert(&i);
+        }
+        bloom.insert("item");
+        bloom.insert(&b"bytes"[..]);
+
+        assert!((0..1000i64).all(|i| bloom.contains(&i)));
+        assert!(bloom.contains(&"item".to_string()));
+        assert!(bloom.contains(&b"bytes".to_vec()));
+        assert!((1000..2000i64).filter(|i| bloom.contains(i)).count() < 50);
+    }
+
+    #[test]
+    fn sizing() {
+        assert_eq!(SplitBlockBloomFilter::new(1, 0.5).num_bytes(), MIN_BITSET_BYTES);
+        assert_eq!(SplitBlockBloomFilter::with_num_bytes(1000).num_bytes(), 1024);
+        assert_eq!(SplitBlockBloomFilter::with_num_bytes(usize::MAX).num_bytes(), MAX_BITSET_BYTES);
+
+        // 1m values at 1% need about 1.2 MB, rounded up to 2 MiB.
+        assert_eq!(SplitBlockBloomFilter::new(1_000_000, 0.01).num_bytes(), 2 * 1024 * 1024);
+        assert!(SplitBlockBloomFilter::try_new(0, 0.01).is_err());
+    }
+
+    #[test]
+    fn mask_sets_one_bit_per_word() {
+        for key in [0, 1, 0xdead_beef, u32::MAX] {
+            assert!(mask(key).iter().all(|word| word.count_ones() == 1));
+        }
+        assert_eq!(mask(0), [1; 8]);
+        assert_eq!(mask(1), SALT.map(|salt| 1 << (salt >> 27)));
+    }
+
+    #[test]
+    fn plain_encoding_hashes() {
+        // xxHash64 of the empty input with seed 0.
+        assert_eq!("".parquet_hash(), 0xef46_db37_51d8_e999);
+        assert_eq!(7i32.parquet_hash(), xxh64(&[7, 0, 0, 0], 0));
+        assert_eq!(7u32.parquet_hash(), 7i32.parquet_hash());
+        assert_eq!(1.5f64.parquet_hash(), xxh64(&1.5f64.to_bits().to_le_bytes(), 0));
+    }
+
+    #[test]
+    fn matches_reference_bitsets() {
+        fn check<V: ParquetValue + ?Sized>(values: &[&V], reference: &str) {
+            let reference = from_hex(reference);
+            let mut bloom = SplitBlockBloomFilter::new(100, 0.01);
+            for value in values {
+                bloom.insert(*value);
+            }
+            assert_eq!(bloom.to_bitset(), reference);
+
+            let restored = SplitBlockBloomFilter::from_bitset(&reference).unwrap();
+            assert!(values.iter().all(|value| restored.contains(*value)));
+            assert_eq!(restored, bloom);
+        }
+
+        check(
+            &[&0i64, &1, &-1, &42, &1_000_000_007, &i64::MAX, &i64::MIN],
+            INT64_BITSET,
+        );
+        check(
+            &["", "hello", "parquet", "bluem", "\u{e9}t\u{e9}", "a", "b"],
+            BYTE_ARRAY_BITSET,
+        );
+        check(
+            &[&0i32, &7, &-7, &i32::MAX, &i32::MIN, &12345, &99],
+            INT32_BITSET,
+        );
+    }
+
+    #[test]
+    fn bitset_round_trip() {
+        let mut bloom = SplitBlockBloomFilter::with_num_bytes(64);
+        bloom.insert_hash(0x0000_0001_0000_0001);
+
+        let bitset = bloom.to_bitset();
+        assert_eq!(bitset.len(), 64);
+        // the upper half of the hash picks block 0 of 2, and the key 1 sets
+        // bit (SALT[i] >> 27) of word i.
+        assert_eq!(&bitset[..4], &(1u32 << (SALT[0] >> 27)).to_le_bytes());
+        assert!(bitset[32..].iter().all(|&byte| byte == 0));
+
+        let restored = SplitBlockBloomFilter::from_bitset(&bitset).unwrap();
+        assert_eq!(restored, bloom);
+        assert!(restored.contains_hash(0x0000_0001_0000_0001));
+
+        assert_eq!(
+            SplitBlockBloomFilter::from_bitset(&bitset[..40]),
+            Err(BloomError::InvalidBitsetLength(40))
+        );
+        assert!(SplitBlockBloomFilter::from_bitset(&[]).is_err());
+    }
+}
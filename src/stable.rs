@@ -0,0 +1,341 @@
+use super::{get_index, hash_kernel, BloomError, SeededState};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+// a stable Bloom filter (Deng & Rafiei, "Approximately Detecting Duplicates
+// for Streaming Data using Stable Bloom Filters", 2006), for detecting
+// duplicates in an unbounded stream.
+//
+// every cell is a small counter. an insert first decrements P randomly
+// chosen cells, then sets the item's k cells to `max`, so old items are
+// gradually forgotten. the share of zero cells converges to a stable point,
+// where the false positive rate stops growing however many items are
+// inserted. in exchange, an item inserted long enough ago can be reported
+// absent.
+//
+// `new(cell_count, fp_rate, max)` creates a filter of `cell_count` cells
+// counting up to `max`, whose false positive rate settles at `fp_rate`: k is
+// the number of hash functions a Bloom filter would use for `fp_rate`, and
+// P is the smallest number of decrements reaching it. it fails if
+// `cell_count` or `max` is zero, or `fp_rate` is not strictly between 0
+// and 1.
+pub struct StableBloomFilter<T: ?Sized, S = SeededState> {
+    // the cells, `bits` bits each, packed into words without straddling.
+    cells: Vec<u64>,
+    cell_count: usize,
+    bits: u32,
+    max: u8,
+    k: u32,
+    p: usize,
+    // xorshift state choosing the cells to decrement.
+    rng: u64,
+    hashers: [S; 2],
+    _marker: PhantomData<fn() -> T>,
+}
+
+seeded_constructors!(
+    StableBloomFilter(cell_count: usize, fp_rate: f64, max: u8) => |hasher1, hasher2| {
+        let (k, p) = parameters(cell_count, fp_rate, max)?;
+        Self::try_with_hashers(cell_count, k, max, p, hasher1, hasher2)
+    }
+);
+
+impl<T: ?Sized> StableBloomFilter<T> {
+    // create a new StableBloomFilter of `cell_count` cells counting up to
+    // `max`, that sets `k` cells and decrements `p` cells per insert. the
+    // hash functions are keyed by random seeds.
+    //
+    // panics if any of the parameters is zero.
+    pub fn with_params(cell_count: usize, k: u32, max: u8, p: usize) -> Self {
+        Self::try_with_params(cell_count, k, max, p).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new StableBloomFilter like `with_params`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_params(
+        cell_count: usize,
+        k: u32,
+        max: u8,
+        p: usize,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_hashers(
+            cell_count,
+            k,
+            max,
+            p,
+            SeededState::new(),
+            SeededState::new(),
+        )
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> StableBloomFilter<T, S> {
+    // create a new StableBloomFilter like `with_params`, deriving its two
+    // hash functions from `hasher1` and `hasher2`.
+    //
+    // panics on the same invalid parameters as `with_params`.
+    pub fn with_hashers(
+        cell_count: usize,
+        k: u32,
+        max: u8,
+        p: usize,
+        hasher1: S,
+        hasher2: S,
+    ) -> Self {
+        Self::try_with_hashers(cell_count, k, max, p, hasher1, hasher2)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // create a new StableBloomFilter like `with_hashers`, returning an error
+    // instead of panicking on invalid parameters.
+    pub fn try_with_hashers(
+        cell_count: usize,
+        k: u32,
+        max: u8,
+        p: usize,
+        hasher1: S,
+        hasher2: S,
+    ) -> Result<Self, BloomError> {
+        if cell_count == 0 {
+            return Err(BloomError::InvalidParameter(
+                "cell_count must be greater than zero",
+            ));
+        }
+        if k == 0 {
+            return Err(BloomError::InvalidParameter("k must be greater than zero"));
+        }
+        if max == 0 {
+            return Err(BloomError::InvalidParameter(
+                "max must be greater than zero",
+            ));
+        }
+        if p == 0 {
+            return Err(BloomError::InvalidParameter("p must be greater than zero"));
+        }
+
+        let bits = u8::BITS - max.leading_zeros();
+        let per_word = (64 / bits) as usize;
+
+        Ok(StableBloomFilter {
+            cells: vec![0; cell_count.div_ceil(per_word)],
+            cell_count,
+            bits,
+            max,
+            k,
+            p,
+            rng: 0x2545_f491_4f6c_dd1d,
+            hashers: [hasher1, hasher2],
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of cells.
+    pub fn cell_count(&self) -> usize {
+        self.cell_count
+    }
+
+    // get the value an insert sets cells to.
+    pub fn max(&self) -> u8 {
+        self.max
+    }
+
+    // get the number of cells an insert sets, i.e. the number of hash
+    // functions.
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+
+    // get the number of cells an insert decrements.
+    pub fn p(&self) -> usize {
+        self.p
+    }
+
+    // calculate the false positive rate the filter converges to as items
+    // are inserted: (1 - (1 / (1 + 1 / (P (1/k - 1/m))))^max)^k.
+    pub fn stable_fp_rate(&self) -> f64 {
+        stable_fp_rate(self.cell_count, self.k, self.max, self.p)
+    }
+
+    // insert items into the set.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        self.test_and_add(item);
+    }
+
+    // check if an item is present in the set.
+    // false positives are possible, and so are false negatives for items
+    // inserted long enough ago.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.indexes(item).all(|index| self.cell(index) > 0)
+    }
+
+    // insert an item, returning whether it was present beforehand, i.e.
+    // whether it is a duplicate in the stream.
+    pub fn test_and_add(&mut self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let indexes: Vec<usize> = self.indexes(item).collect();
+        let present = indexes.iter().all(|&index| self.cell(index) > 0);
+
+        for _ in 0..self.p {
+            let index = self.random_index();
+            let value = self.cell(index);
+            if value > 0 {
+                self.set_cell(index, value - 1);
+            }
+        }
+
+        for index in indexes {
+            self.set_cell(index, self.max);
+        }
+
+        present
+    }
+
+    // get the indexes of the cells of `item`.
+    fn indexes(&self, item: &T) -> impl Iterator<Item = usize>
+    where
+        T: Hash,
+    {
+        let (h1, h2) = hash_kernel(&self.hashers, item);
+        let m = self.cell_count;
+
+        (0..self.k).map(move |k_i| get_index(h1, h2, k_i as u64, m))
+    }
+
+    // get the value of the cell at `index`.
+    fn cell(&self, index: usize) -> u8 {
+        let (word, shift) = self.position(index);
+        ((self.cells[word] >> shift) & self.mask()) as u8
+    }
+
+    // set the cell at `index` to `value`.
+    fn set_cell(&mut self, index: usize, value: u8) {
+        let (word, shift) = self.position(index);
+        let mask = self.mask() << shift;
+        self.cells[word] = (self.cells[word] & !mask) | (value as u64) << shift;
+    }
+
+    // get the word holding the cell at `index` and its offset in the word.
+    fn position(&self, index: usize) -> (usize, u32) {
+        let per_word = (64 / self.bits) as usize;
+        (index / per_word, (index % per_word) as u32 * self.bits)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    // pick a cell uniformly at random.
+    fn random_index(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        ((self.rng as u128 * self.cell_count as u128) >> 64) as usize
+    }
+}
+
+// calculate k and P for `cell_count` cells counting up to `max` that settle
+// at `fp_rate`, by solving the stable point false positive rate for P.
+fn parameters(cell_count: usize, fp_rate: f64, max: u8) -> Result<(u32, usize), BloomError> {
+    if fp_rate.is_nan() {
+        return Err(BloomError::FpRateNotANumber);
+    }
+    if fp_rate <= 0.0 || fp_rate >= 1.0 {
+        return Err(BloomError::FpRateOutOfRange(fp_rate));
+    }
+    if cell_count == 0 {
+        return Err(BloomError::InvalidParameter(
+            "cell_count must be greater than zero",
+        ));
+    }
+
+    let k =
+        ((1.0 / fp_rate).log2().ceil() as u32).clamp(1, cell_count.min(u32::MAX as usize) as u32);
+    let m = cell_count as f64;
+
+    // with fp = (1 - a^max)^k and a = 1 / (1 + 1 / (P (1/k - 1/m))):
+    // P = 1 / ((a^-1 - 1) (1/k - 1/m)).
+    let a = (1.0 - fp_rate.powf(1.0 / k as f64)).powf(1.0 / max as f64);
+    let p = 1.0 / ((1.0 / a - 1.0) * (1.0 / k as f64 - 1.0 / m));
+    let p = if p.is_finite() {
+        p.ceil().clamp(1.0, m) as usize
+    } else {
+        1
+    };
+
+    Ok((k, p))
+}
+
+fn stable_fp_rate(cell_count: usize, k: u32, max: u8, p: usize) -> f64 {
+    let (m, k) = (cell_count as f64, k as f64);
+    let a = 1.0 / (1.0 + 1.0 / (p as f64 * (1.0 / k - 1.0 / m)));
+
+    (1.0 - a.powi(max as i32)).powf(k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let mut stable = StableBloomFilter::new(1000, 0.01, 1);
+        assert!(!stable.contains("item"));
+        stable.insert("item");
+        assert!(stable.contains("item"));
+    }
+
+    #[test]
+    fn test_and_add() {
+        let mut stable = StableBloomFilter::new(1000, 0.01, 3);
+        assert!(!stable.test_and_add("item"));
+        assert!(stable.test_and_add("item"));
+    }
+
+    #[test]
+    fn parameters() {
+        let stable: StableBloomFilter<i32> = StableBloomFilter::new(10_000, 0.01, 1);
+        assert_eq!(stable.k(), 7);
+        assert_eq!(stable.max(), 1);
+        assert_eq!(stable.p(), 7);
+        // P is rounded up, which only lowers the rate.
+        assert!(stable.stable_fp_rate() <= 0.01);
+        assert!(stable.stable_fp_rate() > 0.007);
+
+        let stable: StableBloomFilter<i32> = StableBloomFilter::new(10_000, 0.01, 7);
+        assert!(stable.p() > 7);
+        assert!(stable.stable_fp_rate() <= 0.01);
+
+        assert_eq!(
+            StableBloomFilter::<i32>::try_with_params(100, 3, 0, 10).err(),
+            Some(BloomError::InvalidParameter(
+                "max must be greater than zero"
+            ))
+        );
+        assert!(StableBloomFilter::<i32>::try_new(0, 0.01, 1).is_err());
+        assert!(StableBloomFilter::<i32>::try_new(100, 1.0, 1).is_err());
+    }
+
+    #[test]
+    fn converges_to_stable_fp_rate() {
+        let mut stable = StableBloomFilter::with_seeds(10_000, 0.01, 3, 1, 2);
+        for i in 0..200_000 {
+            stable.insert(&i);
+        }
+
+        // recent items are remembered, and the filter has not filled up.
+        assert!((199_900..200_000).all(|i| stable.contains(&i)));
+        let false_positives = (1_000_000..1_100_000)
+            .filter(|i| stable.contains(i))
+            .count();
+        let fp_rate = false_positives as f64 / 100_000.0;
+        assert!((fp_rate - stable.stable_fp_rate()).abs() < 0.004);
+    }
+}
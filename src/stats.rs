@@ -0,0 +1,136 @@
+use super::{bitmap_size, BloomFilter};
+use std::hash::BuildHasher;
+
+// a snapshot of how full a BloomFilter is and how that affects its false
+// positive rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomStats {
+    // the number of bits in the bitmap.
+    pub optimal_m: usize,
+    // the number of hash functions.
+    pub optimal_k: u32,
+    // the number of bits set in the bitmap.
+    pub bits_set: u64,
+    // the fraction of the bitmap that is set, between 0 and 1.
+    pub fill_ratio: f64,
+    // the number of times `insert` has been called.
+    pub insertions: u64,
+    // the false positive rate the filter was created for.
+    pub design_fp_rate: f64,
+    // the number of items the filter was created for, recovered from
+    // `optimal_m` and `design_fp_rate`.
+    pub capacity: u64,
+    // the false positive rate expected after `insertions` insertions,
+    // (1 - e^(-k * n / m))^k. as `optimal_k` is rounded up, it can reach
+    // `design_fp_rate` slightly before the filter holds `capacity` items.
+    pub current_fp_rate: f64,
+}
+
+impl BloomStats {
+    // check if the filter holds more items than it was created for, so its
+    // false positive rate has degraded past the one it was created for.
+    pub fn is_over_capacity(&self) -> bool {
+        self.insertions > self.capacity
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> BloomFilter<T, S> {
+    // get statistics about the filter's saturation and false positive rate.
+    pub fn stats(&self) -> BloomStats {
+        let m = self.optimal_m as f64;
+        let k = self.optimal_k as f64;
+        let n = self.insertions as f64;
+        let bits_set = self.bitmap.count_ones();
+
+        BloomStats {
+            optimal_m: self.optimal_m,
+            optimal_k: self.optimal_k,
+            bits_set,
+            fill_ratio: bits_set as f64 / m,
+            insertions: self.insertions,
+            design_fp_rate: self.fp_rate,
+            capacity: capacity(self.optimal_m, self.fp_rate),
+            current_fp_rate: (1.0 - (-k * n / m).exp()).powf(k),
+        }
+    }
+}
+
+// get the largest items count for which `bitmap_size` is at most
+// `optimal_m`, starting from the estimate that inverts its formula.
+fn capacity(optimal_m: usize, fp_rate: f64) -> u64 {
+    let ln2_2 = core::f64::consts::LN_2 * core::f64::consts::LN_2;
+    let fits =
+        |items_count: usize| bitmap_size(items_count, fp_rate).is_some_and(|m| m <= optimal_m);
+
+    let mut items_count = (optimal_m as f64 * ln2_2 / -fp_rate.ln()) as usize;
+    while items_count > 0 && !fits(items_count) {
+        items_count -= 1;
+    }
+    while items_count.checked_add(1).is_some_and(fits) {
+        items_count += 1;
+    }
+
+    items_count as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_filter() {
+        let bloom: BloomFilter<i32> = BloomFilter::new(1000, 0.01);
+        let stats = bloom.stats();
+
+        assert_eq!(stats.optimal_m, bloom.optimal_m);
+        assert_eq!(stats.optimal_k, bloom.optimal_k);
+        assert_eq!(stats.bits_set, 0);
+        assert_eq!(stats.fill_ratio, 0.0);
+        assert_eq!(stats.insertions, 0);
+        assert_eq!(stats.design_fp_rate, 0.01);
+        assert_eq!(stats.capacity, 1000);
+        assert_eq!(stats.current_fp_rate, 0.0);
+        assert!(!stats.is_over_capacity());
+    }
+
+    #[test]
+    fn full_at_design_load() {
+        for (items_count, fp_rate) in [(1000, 0.01), (1, 0.5), (12_345, 0.001), (100, 1e-9)] {
+            let mut bloom: BloomFilter<usize> = BloomFilter::with_seeds(items_count, fp_rate, 1, 2);
+            for i in 0..items_count {
+                bloom.insert(&i);
+            }
+
+            let stats = bloom.stats();
+            assert_eq!(stats.capacity, items_count as u64);
+            assert!(!stats.is_over_capacity());
+
+            bloom.insert(&items_count);
+            assert!(bloom.stats().is_over_capacity());
+        }
+    }
+
+    #[test]
+    fn degrades_past_capacity() {
+        let mut bloom: BloomFilter<i32> = BloomFilter::new(1000, 0.01);
+        for i in 0..900 {
+            bloom.insert(&i);
+        }
+
+        let stats = bloom.stats();
+        assert_eq!(stats.insertions, 900);
+        assert!(stats.fill_ratio > 0.4 && stats.fill_ratio < 0.6);
+        assert!(stats.current_fp_rate <= stats.design_fp_rate);
+        assert!(!stats.is_over_capacity());
+
+        for i in 900..3000 {
+            bloom.insert(&i);
+        }
+
+        let stats = bloom.stats();
+        assert_eq!(stats.insertions, 3000);
+        assert!(stats.bits_set <= stats.optimal_m as u64);
+        assert!(stats.current_fp_rate > 0.1);
+        assert!(stats.is_over_capacity());
+    }
+}
@@ -0,0 +1,396 @@
+// a versioned binary format for BloomFilters keyed by `SeededState`.
+//
+// all integers are little-endian. version 2 is laid out as:
+//
+//   offset  size  field
+//        0     4  magic bytes, b"BLUM"
+//        4     1  format version, 2
+//        5     1  hash algorithm id, 1 (SipHash-1-3 keyed by a seed)
+//        6     2  reserved, zero
+//        8     8  seed of the first hash function
+//       16     8  seed of the second hash function
+//       24     8  optimal_m, the number of bits in the bitmap
+//       32     4  optimal_k, the number of hash functions
+//       36     8  fp_rate, the false positive rate the filter was created
+//                 for, as an IEEE 754 double
+//       44     8  item count, the number of insertions
+//       52  8 * w bitmap, w = ceil(optimal_m / 64) words. bit i of the
+//                 bitmap is bit (i % 64) of word (i / 64); unused bits of
+//                 the last word are zero.
+//  52 + 8w     4  CRC32C of all preceding bytes
+//
+// version 1 is the same without the fp_rate field: the item count is at
+// offset 36 and the bitmap at 44. it is still read, taking fp_rate to be
+// 2^-optimal_k, the rate a filter's optimal_m and optimal_k are optimal for.
+
+use super::{bitmap_from_words, bitmap_words, BloomFilter, SeededState, MAX_OPTIMAL_K};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+const MAGIC: [u8; 4] = *b"BLUM";
+const FORMAT_VERSION: u8 = 2;
+const SIPHASH_1_3: u8 = 1;
+const HEADER_LEN: usize = 52;
+const V1_HEADER_LEN: usize = 44;
+
+// the error returned when a BloomFilter cannot be read from its binary format.
+#[derive(Debug)]
+pub enum WireError {
+    // reading from the underlying reader failed.
+    Io(io::Error),
+    // the input ended before the whole filter was read.
+    Truncated,
+    // the input does not start with the magic bytes.
+    BadMagic,
+    // the input was written in a format version this crate cannot read.
+    UnsupportedVersion(u8),
+    // the filter was written with a hash algorithm this crate does not implement.
+    UnsupportedHashAlgorithm(u8),
+    // the checksum stored in the input does not match its contents.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    // the input is well-formed but describes an impossible filter.
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Io(err) => write!(f, "failed to read bloom filter: {}", err),
+            WireError::Truncated => f.write_str("bloom filter data is truncated"),
+            WireError::BadMagic => f.write_str("data is not a bloom filter"),
+            WireError::UnsupportedVersion(version) => {
+                write!(f, "unsupported bloom filter format version {}", version)
+            }
+            WireError::UnsupportedHashAlgorithm(id) => {
+                write!(f, "unsupported bloom filter hash algorithm {}", id)
+            }
+            WireError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "bloom filter checksum mismatch: expected {:#010x}, got {:#010x}",
+                expected, actual
+            ),
+            WireError::Corrupt(reason) => write!(f, "corrupt bloom filter: {}", reason),
+        }
+    }
+}
+
+impl Error for WireError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WireError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WireError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            WireError::Truncated
+        } else {
+            WireError::Io(err)
+        }
+    }
+}
+
+impl<T: ?Sized> BloomFilter<T> {
+    // encode the filter in the binary format described at the top of this module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let words = self.optimal_m.div_ceil(64);
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * words + 4);
+        let (seed1, seed2) = self.seeds();
+
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.push(SIPHASH_1_3);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&seed1.to_le_bytes());
+        bytes.extend_from_slice(&seed2.to_le_bytes());
+        bytes.extend_from_slice(&(self.optimal_m as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.optimal_k.to_le_bytes());
+        bytes.extend_from_slice(&self.fp_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.insertions.to_le_bytes());
+
+        for word in bitmap_words(&self.bitmap) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let checksum = crc32c(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    // write the filter to `writer` in its binary format.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    // decode a filter from `bytes`, which must hold exactly one encoded filter.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, WireError> {
+        let filter = Self::read_from(&mut bytes)?;
+
+        if !bytes.is_empty() {
+            return Err(WireError::Corrupt("trailing bytes after checksum"));
+        }
+
+        Ok(filter)
+    }
+
+    // read a filter in its binary format from `reader`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, WireError> {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header[..8])?;
+
+        if header[0..4] != MAGIC {
+            return Err(WireError::BadMagic);
+        }
+        let header_len = match header[4] {
+            1 => V1_HEADER_LEN,
+            FORMAT_VERSION => HEADER_LEN,
+            version => return Err(WireError::UnsupportedVersion(version)),
+        };
+        if header[5] != SIPHASH_1_3 {
+            return Err(WireError::UnsupportedHashAlgorithm(header[5]));
+        }
+
+        let header = &mut header[..header_len];
+        reader.read_exact(&mut header[8..])?;
+
+        let seed1 = read_u64(&header[8..16]);
+        let seed2 = read_u64(&header[16..24]);
+        let optimal_m = read_u64(&header[24..32]);
+        let optimal_k = u32::from_le_bytes([header[32], header[33], header[34], header[35]]);
+
+        if optimal_m == 0 {
+            return Err(WireError::Corrupt("optimal_m is zero"));
+        }
+        if optimal_k == 0 || optimal_k > MAX_OPTIMAL_K {
+            return Err(WireError::Corrupt("optimal_k is out of range"));
+        }
+
+        let (fp_rate, insertions) = if header_len == V1_HEADER_LEN {
+            (0.5f64.powi(optimal_k as i32), read_u64(&header[36..44]))
+        } else {
+            (f64::from_bits(read_u64(&header[36..44])), read_u64(&header[44..52]))
+        };
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            return Err(WireError::Corrupt("fp_rate is not between 0 and 1"));
+        }
+        let optimal_m = usize::try_from(optimal_m)
+            .map_err(|_| WireError::Corrupt("optimal_m does not fit in memory"))?;
+
+        // read the bitmap and checksum through `take` so a corrupt length
+        // cannot make us allocate more than the input actually holds.
+        let body_len = optimal_m.div_ceil(64) as u64 * 8 + 4;
+        let mut body = Vec::new();
+        reader.by_ref().take(body_len).read_to_end(&mut body)?;
+        if (body.len() as u64) < body_len {
+            return Err(WireError::Truncated);
+        }
+
+        let (words, checksum) = body.split_at(body.len() - 4);
+        let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        let actual = crc32c_update(crc32c(header), words);
+        if expected != actual {
+            return Err(WireError::ChecksumMismatch { expected, actual });
+        }
+
+        let words: Vec<u64> = words.chunks(8).map(read_u64).collect();
+        let unused_bits = words.len() * 64 - optimal_m;
+        if unused_bits > 0 && words[words.len() - 1] >> (64 - unused_bits) != 0 {
+            return Err(WireError::Corrupt("bits set past optimal_m"));
+        }
+
+        Ok(BloomFilter {
+            bitmap: bitmap_from_words(&words, optimal_m),
+            optimal_m,
+            optimal_k,
+            fp_rate,
+            insertions,
+            hashers: [SeededState::with_seed(seed1), SeededState::with_seed(seed2)],
+            _marker: PhantomData,
+        })
+    }
+}
+
+pub(crate) fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+// CRC-32C (Castagnoli), reflected polynomial 0x82f63b78.
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub(crate) fn crc32c(bytes: &[u8]) -> u32 {
+    crc32c_update(0, bytes)
+}
+
+// continue the checksum `crc` of some bytes over `bytes`.
+pub(crate) fn crc32c_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> BloomFilter<i32> {
+        let mut bloom = BloomFilter::with_seeds(1000, 0.01, 3, 5);
+        for i in 0..300 {
+            bloom.insert(&i);
+        }
+        bloom
+    }
+
+    #[test]
+    fn crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c_update(crc32c(b"1234"), b"56789"), 0xe306_9283);
+    }
+
+    #[test]
+    fn round_trip() {
+        let bloom = filter();
+        let bytes = bloom.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 8 * bloom.optimal_m.div_ceil(64) + 4);
+
+        let restored = BloomFilter::<i32>::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.seeds(), (3, 5));
+        assert_eq!(restored.optimal_m, bloom.optimal_m);
+        assert_eq!(restored.optimal_k, bloom.optimal_k);
+        assert_eq!(restored.fp_rate(), 0.01);
+        assert_eq!(restored.insertions(), 300);
+        assert_eq!(restored.bitmap, bloom.bitmap);
+        assert!((0..300).all(|i| restored.contains(&i)));
+
+        let mut written = Vec::new();
+        bloom.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+        assert_eq!(BloomFilter::<i32>::read_from(&written[..]).unwrap().bitmap, bloom.bitmap);
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let bloom = filter();
+        let v2 = bloom.to_bytes();
+
+        // the version 1 encoding: no fp_rate, checksummed over its own bytes.
+        let mut v1 = v2[..36].to_vec();
+        v1[4] = 1;
+        v1.extend_from_slice(&v2[44..v2.len() - 4]);
+        let checksum = crc32c(&v1);
+        v1.extend_from_slice(&checksum.to_le_bytes());
+
+        let restored = BloomFilter::<i32>::from_bytes(&v1).unwrap();
+        assert_eq!(restored.seeds(), (3, 5));
+        assert_eq!(restored.optimal_k, bloom.optimal_k);
+        assert_eq!(restored.fp_rate(), 0.5f64.powi(bloom.optimal_k as i32));
+        assert_eq!(restored.insertions(), 300);
+        assert_eq!(restored.bitmap, bloom.bitmap);
+
+        for len in [V1_HEADER_LEN - 1, V1_HEADER_LEN, v1.len() - 1] {
+            assert!(matches!(
+                BloomFilter::<i32>::from_bytes(&v1[..len]),
+                Err(WireError::Truncated)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let bytes = filter().to_bytes();
+        for len in [0, 3, HEADER_LEN - 1, HEADER_LEN, bytes.len() - 1] {
+            assert!(matches!(
+                BloomFilter::<i32>::from_bytes(&bytes[..len]),
+                Err(WireError::Truncated)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_incompatible_input() {
+        let bytes = filter().to_bytes();
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert!(matches!(BloomFilter::<i32>::from_bytes(&bad), Err(WireError::BadMagic)));
+
+        let mut bad = bytes.clone();
+        bad[4] = 3;
+        assert!(matches!(
+            BloomFilter::<i32>::from_bytes(&bad),
+            Err(WireError::UnsupportedVersion(3))
+        ));
+
+        let mut bad = bytes.clone();
+        bad[5] = 9;
+        assert!(matches!(
+            BloomFilter::<i32>::from_bytes(&bad),
+            Err(WireError::UnsupportedHashAlgorithm(9))
+        ));
+
+        let mut bad = bytes.clone();
+        bad.push(0);
+        assert!(matches!(BloomFilter::<i32>::from_bytes(&bad), Err(WireError::Corrupt(_))));
+    }
+
+    #[test]
+    fn rejects_corrupt_input() {
+        let bytes = filter().to_bytes();
+
+        let mut bad = bytes.clone();
+        bad[HEADER_LEN + 3] ^= 0x10;
+        assert!(matches!(
+            BloomFilter::<i32>::from_bytes(&bad),
+            Err(WireError::ChecksumMismatch { .. })
+        ));
+
+        let mut bad = bytes.clone();
+        bad[8] ^= 0x01;
+        assert!(matches!(
+            BloomFilter::<i32>::from_bytes(&bad),
+            Err(WireError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_optimal_k() {
+        let bytes = filter().to_bytes();
+
+        // well-formed inputs, checksummed after patching optimal_k.
+        for optimal_k in [0, MAX_OPTIMAL_K + 1, u32::MAX] {
+            let mut bad = bytes[..bytes.len() - 4].to_vec();
+            bad[32..36].copy_from_slice(&optimal_k.to_le_bytes());
+            let checksum = crc32c(&bad);
+            bad.extend_from_slice(&checksum.to_le_bytes());
+
+            assert!(matches!(
+                BloomFilter::<i32>::from_bytes(&bad),
+                Err(WireError::Corrupt("optimal_k is out of range"))
+            ));
+        }
+    }
+}
@@ -0,0 +1,763 @@
+// static filters built once from a complete set of keys: xor filters (Graf
+// & Lemire, "Xor Filters: Faster and Smaller Than Bloom and Cuckoo Filters",
+// 2020) and binary fuse filters (Graf & Lemire, "Binary Fuse Filters: Fast
+// and Smaller Than Xor Filters", 2022).
+//
+// both store one fingerprint per slot, chosen so that the fingerprints of a
+// key's three slots xor to the key's own fingerprint. with 8-bit
+// fingerprints they use about 9.8 (xor) and, for large key sets, 9.0
+// (binary fuse) bits per key for a false positive rate of 1/256, where a
+// BloomFilter needs about 12.
+//
+// built filters encode to bytes, little-endian, laid out as:
+//
+//   offset  size  field
+//        0     4  magic bytes, b"BLXR" (xor) or b"BLBF" (binary fuse)
+//        4     1  format version, 1
+//        5     1  fingerprint bits, 8 or 16
+//        6     2  reserved, zero
+//        8     8  seed of the `SeededState` the keys are hashed with
+//       16     8  seed the key hashes were mixed with during construction
+//       24     8  number of keys
+//       32     8  xor: the block length; binary fuse: the segment length
+//                 in the low 32 bits and the segment count in the high 32
+//       40     8  f, the number of fingerprints
+//       48  f * b fingerprints, b = fingerprint bits / 8 bytes each
+//   48 + fb    4  CRC32C of all preceding bytes
+
+use super::wire::{crc32c, crc32c_update, read_u64};
+use super::{BloomError, SeededState, WireError};
+use std::hash::{BuildHasher, Hash};
+use std::io::Read;
+use std::marker::PhantomData;
+use std::ops::BitXor;
+
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 48;
+const XOR_MAGIC: [u8; 4] = *b"BLXR";
+const BINARY_FUSE_MAGIC: [u8; 4] = *b"BLBF";
+// how many seeds construction tries before giving up. with distinct keys
+// each attempt succeeds with high probability, so this is never reached in
+// practice.
+const MAX_ATTEMPTS: usize = 100;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+}
+
+// the fingerprint stored in each slot of a static filter: `u8` for a false
+// positive rate of 1/256, `u16` for 1/65536.
+pub trait Fingerprint: Copy + Default + Eq + BitXor<Output = Self> + sealed::Sealed {
+    const BITS: u32;
+
+    // get the fingerprint of a (mixed) key hash.
+    fn from_hash(hash: u64) -> Self;
+
+    fn write_le(self, bytes: &mut Vec<u8>);
+
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+impl Fingerprint for u8 {
+    const BITS: u32 = 8;
+
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u8
+    }
+
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.push(self);
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+}
+
+impl Fingerprint for u16 {
+    const BITS: u32 = 16;
+
+    fn from_hash(hash: u64) -> Self {
+        (hash ^ (hash >> 32)) as u16
+    }
+
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    }
+}
+
+// an immutable xor filter over a fixed set of keys.
+pub struct XorFilter<T: ?Sized, F, S = SeededState> {
+    seed: u64,
+    block_length: usize,
+    fingerprints: Vec<F>,
+    len: usize,
+    hasher: S,
+    _marker: PhantomData<fn() -> T>,
+}
+
+pub type XorFilter8<T, S = SeededState> = XorFilter<T, u8, S>;
+pub type XorFilter16<T, S = SeededState> = XorFilter<T, u16, S>;
+
+// an immutable binary fuse filter over a fixed set of keys.
+pub struct BinaryFuseFilter<T: ?Sized, F, S = SeededState> {
+    seed: u64,
+    segment_length: usize,
+    segment_count: usize,
+    fingerprints: Vec<F>,
+    len: usize,
+    hasher: S,
+    _marker: PhantomData<fn() -> T>,
+}
+
+pub type BinaryFuseFilter8<T, S = SeededState> = BinaryFuseFilter<T, u8, S>;
+pub type BinaryFuseFilter16<T, S = SeededState> = BinaryFuseFilter<T, u16, S>;
+
+impl<T: ?Sized + Hash, F: Fingerprint> XorFilter<T, F> {
+    // build a XorFilter holding `keys`, hashing them with a random seed.
+    // duplicate keys are stored once.
+    //
+    // panics if the filter cannot be built; see `try_new`.
+    pub fn new<'a, I>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::with_hasher(keys, SeededState::new())
+    }
+
+    // build a XorFilter like `new`, returning an error if there are too
+    // many keys or no seed peels them.
+    pub fn try_new<'a, I>(keys: I) -> Result<Self, BloomError>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::try_with_hasher(keys, SeededState::new())
+    }
+
+    // encode the filter in the format described at the top of this module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let params = self.block_length as u64;
+        encode(
+            XOR_MAGIC,
+            self.hasher.seed(),
+            self.seed,
+            self.len,
+            params,
+            &self.fingerprints,
+        )
+    }
+
+    // decode a filter from `bytes`, which must hold exactly one encoded filter.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, WireError> {
+        let filter = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(WireError::Corrupt("trailing bytes after checksum"));
+        }
+        Ok(filter)
+    }
+
+    // read a filter in its encoded format from `reader`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, WireError> {
+        let decoded = decode::<F, R>(reader, XOR_MAGIC)?;
+        // construction always makes at least 10 slots per block; an empty
+        // filter would have nowhere for `contains` to look.
+        if decoded.params == 0 {
+            return Err(WireError::Corrupt("block length is zero"));
+        }
+        let block_length = usize::try_from(decoded.params)
+            .ok()
+            .filter(|&length| {
+                length <= u32::MAX as usize
+                    && length.checked_mul(3) == Some(decoded.fingerprints.len())
+            })
+            .ok_or(WireError::Corrupt(
+                "fingerprint count does not match block length",
+            ))?;
+
+        Ok(XorFilter {
+            seed: decoded.seed,
+            block_length,
+            fingerprints: decoded.fingerprints,
+            len: decoded.len,
+            hasher: SeededState::with_seed(decoded.hasher_seed),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized + Hash, F: Fingerprint, S: BuildHasher> XorFilter<T, F, S> {
+    // build a XorFilter like `new`, hashing keys with `hasher`.
+    //
+    // panics if the filter cannot be built; see `try_new`.
+    pub fn with_hasher<'a, I>(keys: I, hasher: S) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::try_with_hasher(keys, hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // build a XorFilter like `with_hasher`, returning an error if there are
+    // too many keys or no seed peels them.
+    pub fn try_with_hasher<'a, I>(keys: I, hasher: S) -> Result<Self, BloomError>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        let hashes = distinct_hashes(keys, &hasher);
+        let capacity = (32.0 + 1.23 * hashes.len() as f64).ceil() as usize;
+        let block_length = capacity / 3;
+        if block_length > u32::MAX as usize {
+            return Err(BloomError::BitmapTooLarge);
+        }
+
+        let (seed, fingerprints) = build(&hashes, 3 * block_length, |hash| {
+            xor_positions(hash, block_length)
+        })?;
+
+        Ok(XorFilter {
+            seed,
+            block_length,
+            fingerprints,
+            len: hashes.len(),
+            hasher,
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of distinct keys the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // check if the filter was built from no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // get the number of bits the filter uses per key.
+    pub fn bits_per_key(&self) -> f64 {
+        bits_per_key::<F>(self.fingerprints.len(), self.len)
+    }
+
+    // check if an item is one of the keys.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool {
+        let hash = mix(self.hasher.hash_one(item), self.seed);
+        let [p0, p1, p2] = xor_positions(hash, self.block_length);
+        let fingerprints = &self.fingerprints;
+
+        F::from_hash(hash) == fingerprints[p0] ^ fingerprints[p1] ^ fingerprints[p2]
+    }
+}
+
+impl<T: ?Sized + Hash, F: Fingerprint> BinaryFuseFilter<T, F> {
+    // build a BinaryFuseFilter holding `keys`, hashing them with a random
+    // seed. duplicate keys are stored once.
+    //
+    // panics if the filter cannot be built; see `try_new`.
+    pub fn new<'a, I>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::with_hasher(keys, SeededState::new())
+    }
+
+    // build a BinaryFuseFilter like `new`, returning an error if there are
+    // too many keys or no seed peels them.
+    pub fn try_new<'a, I>(keys: I) -> Result<Self, BloomError>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::try_with_hasher(keys, SeededState::new())
+    }
+
+    // encode the filter in the format described at the top of this module.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let params = self.segment_length as u64 | (self.segment_count as u64) << 32;
+        encode(
+            BINARY_FUSE_MAGIC,
+            self.hasher.seed(),
+            self.seed,
+            self.len,
+            params,
+            &self.fingerprints,
+        )
+    }
+
+    // decode a filter from `bytes`, which must hold exactly one encoded filter.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, WireError> {
+        let filter = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(WireError::Corrupt("trailing bytes after checksum"));
+        }
+        Ok(filter)
+    }
+
+    // read a filter in its encoded format from `reader`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, WireError> {
+        let decoded = decode::<F, R>(reader, BINARY_FUSE_MAGIC)?;
+        let segment_length = (decoded.params & 0xffff_ffff) as usize;
+        let segment_count = (decoded.params >> 32) as usize;
+
+        if !segment_length.is_power_of_two() || segment_count == 0 {
+            return Err(WireError::Corrupt("invalid segment length or count"));
+        }
+        if (segment_count + 2).checked_mul(segment_length) != Some(decoded.fingerprints.len()) {
+            return Err(WireError::Corrupt(
+                "fingerprint count does not match segments",
+            ));
+        }
+
+        Ok(BinaryFuseFilter {
+            seed: decoded.seed,
+            segment_length,
+            segment_count,
+            fingerprints: decoded.fingerprints,
+            len: decoded.len,
+            hasher: SeededState::with_seed(decoded.hasher_seed),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized + Hash, F: Fingerprint, S: BuildHasher> BinaryFuseFilter<T, F, S> {
+    // build a BinaryFuseFilter like `new`, hashing keys with `hasher`.
+    //
+    // panics if the filter cannot be built; see `try_new`.
+    pub fn with_hasher<'a, I>(keys: I, hasher: S) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        Self::try_with_hasher(keys, hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // build a BinaryFuseFilter like `with_hasher`, returning an error if
+    // there are too many keys or no seed peels them.
+    pub fn try_with_hasher<'a, I>(keys: I, hasher: S) -> Result<Self, BloomError>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        let hashes = distinct_hashes(keys, &hasher);
+        let (segment_length, segment_count) = fuse_dimensions(hashes.len())?;
+        let segment_count_length = segment_count * segment_length;
+
+        let (seed, fingerprints) = build(&hashes, (segment_count + 2) * segment_length, |hash| {
+            fuse_positions(hash, segment_length, segment_count_length)
+        })?;
+
+        Ok(BinaryFuseFilter {
+            seed,
+            segment_length,
+            segment_count,
+            fingerprints,
+            len: hashes.len(),
+            hasher,
+            _marker: PhantomData,
+        })
+    }
+
+    // get the number of distinct keys the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // check if the filter was built from no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // get the number of bits the filter uses per key.
+    pub fn bits_per_key(&self) -> f64 {
+        bits_per_key::<F>(self.fingerprints.len(), self.len)
+    }
+
+    // check if an item is one of the keys.
+    // false positives are possible, but not false negatives.
+    pub fn contains(&self, item: &T) -> bool {
+        let hash = mix(self.hasher.hash_one(item), self.seed);
+        let segment_count_length = self.segment_count * self.segment_length;
+        let [p0, p1, p2] = fuse_positions(hash, self.segment_length, segment_count_length);
+        let fingerprints = &self.fingerprints;
+
+        F::from_hash(hash) == fingerprints[p0] ^ fingerprints[p1] ^ fingerprints[p2]
+    }
+}
+
+// hash `keys`, dropping duplicates: two equal hashes always land in the
+// same slots, so no seed could peel them apart.
+fn distinct_hashes<'a, T, S, I>(keys: I, hasher: &S) -> Vec<u64>
+where
+    T: ?Sized + Hash + 'a,
+    S: BuildHasher,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut hashes: Vec<u64> = keys.into_iter().map(|key| hasher.hash_one(key)).collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+// try seeds until the mixed key hashes peel, then assign the fingerprints.
+// returns the seed that worked and the fingerprints.
+fn build<F: Fingerprint>(
+    hashes: &[u64],
+    slot_count: usize,
+    positions: impl Fn(u64) -> [usize; 3],
+) -> Result<(u64, Vec<F>), BloomError> {
+    let mut rng = 0x726b_2b9d_438b_9d4d;
+
+    for _ in 0..MAX_ATTEMPTS {
+        let seed = splitmix64(&mut rng);
+        let mixed: Vec<u64> = hashes.iter().map(|&hash| mix(hash, seed)).collect();
+
+        if let Some(order) = peel(&mixed, slot_count, &positions) {
+            let mut fingerprints = vec![F::default(); slot_count];
+
+            // assign in reverse peeling order, so every slot a key's
+            // fingerprint depends on is final by the time it is computed.
+            for &(hash, slot) in order.iter().rev() {
+                let [p0, p1, p2] = positions(hash);
+                let others = fingerprints[p0] ^ fingerprints[p1] ^ fingerprints[p2];
+                fingerprints[slot] = F::from_hash(hash) ^ others ^ fingerprints[slot];
+            }
+
+            return Ok((seed, fingerprints));
+        }
+    }
+
+    Err(BloomError::ConstructionFailed)
+}
+
+// peel the hypergraph whose edges are the slots of each hash: repeatedly
+// take a slot used by a single remaining hash and assign the hash to it.
+// returns each hash with its slot in peeling order, or `None` if some hashes
+// could not be peeled.
+fn peel(
+    hashes: &[u64],
+    slot_count: usize,
+    positions: &impl Fn(u64) -> [usize; 3],
+) -> Option<Vec<(u64, usize)>> {
+    let mut counts = vec![0u32; slot_count];
+    let mut xors = vec![0u64; slot_count];
+
+    for &hash in hashes {
+        for position in positions(hash) {
+            counts[position] += 1;
+            xors[position] ^= hash;
+        }
+    }
+
+    let mut queue: Vec<usize> = (0..slot_count).filter(|&slot| counts[slot] == 1).collect();
+    let mut order = Vec::with_capacity(hashes.len());
+
+    while let Some(slot) = queue.pop() {
+        if counts[slot] != 1 {
+            continue;
+        }
+
+        // the only hash left in a slot is the xor of all hashes in it.
+        let hash = xors[slot];
+        order.push((hash, slot));
+
+        for position in positions(hash) {
+            counts[position] -= 1;
+            xors[position] ^= hash;
+            if counts[position] == 1 {
+                queue.push(position);
+            }
+        }
+    }
+
+    (order.len() == hashes.len()).then_some(order)
+}
+
+// get the slots of `hash` in an xor filter: one in each of three blocks.
+fn xor_positions(hash: u64, block_length: usize) -> [usize; 3] {
+    let reduce = |x: u64| ((x as u32 as u64 * block_length as u64) >> 32) as usize;
+
+    [
+        reduce(hash),
+        reduce(hash.rotate_left(21)) + block_length,
+        reduce(hash.rotate_left(42)) + 2 * block_length,
+    ]
+}
+
+// get the slots of `hash` in a binary fuse filter: one in each of three
+// consecutive segments.
+fn fuse_positions(hash: u64, segment_length: usize, segment_count_length: usize) -> [usize; 3] {
+    let mask = segment_length as u64 - 1;
+    let p0 = ((hash as u128 * segment_count_length as u128) >> 64) as u64;
+    let p1 = (p0 + segment_length as u64) ^ ((hash >> 18) & mask);
+    let p2 = (p0 + 2 * segment_length as u64) ^ (hash & mask);
+
+    [p0 as usize, p1 as usize, p2 as usize]
+}
+
+// calculate the segment length and count of a 3-wise binary fuse filter
+// for `len` keys, as in the reference implementation.
+fn fuse_dimensions(len: usize) -> Result<(usize, usize), BloomError> {
+    let n = len as f64;
+    let segment_length = if len > 1 {
+        let exponent = (n.ln() / 3.33f64.ln() + 2.25).floor() as u32;
+        1usize << exponent.min(18)
+    } else {
+        4
+    };
+    let capacity = if len > 1 {
+        let size_factor = (0.875 + 0.25 * 1e6f64.ln() / n.ln()).max(1.125);
+        (n * size_factor).round() as usize
+    } else {
+        0
+    };
+
+    let initial_segments = capacity.div_ceil(segment_length).saturating_sub(2);
+    let segment_count = initial_segments.max(1);
+    if segment_count > u32::MAX as usize {
+        return Err(BloomError::BitmapTooLarge);
+    }
+
+    Ok((segment_length, segment_count))
+}
+
+// mix a key hash with the construction seed (the murmur3 64-bit finalizer).
+fn mix(hash: u64, seed: u64) -> u64 {
+    let mut h = hash.wrapping_add(seed);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn bits_per_key<F: Fingerprint>(fingerprints: usize, len: usize) -> f64 {
+    (fingerprints as u64 * F::BITS as u64) as f64 / len.max(1) as f64
+}
+
+// the header fields and fingerprints of an encoded static filter.
+struct Decoded<F> {
+    hasher_seed: u64,
+    seed: u64,
+    len: usize,
+    params: u64,
+    fingerprints: Vec<F>,
+}
+
+fn encode<F: Fingerprint>(
+    magic: [u8; 4],
+    hasher_seed: u64,
+    seed: u64,
+    len: usize,
+    params: u64,
+    fingerprints: &[F],
+) -> Vec<u8> {
+    let width = F::BITS as usize / 8;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + width * fingerprints.len() + 4);
+
+    bytes.extend_from_slice(&magic);
+    bytes.push(FORMAT_VERSION);
+    bytes.push(F::BITS as u8);
+    bytes.extend_from_slice(&[0, 0]);
+    bytes.extend_from_slice(&hasher_seed.to_le_bytes());
+    bytes.extend_from_slice(&seed.to_le_bytes());
+    bytes.extend_from_slice(&(len as u64).to_le_bytes());
+    bytes.extend_from_slice(&params.to_le_bytes());
+    bytes.extend_from_slice(&(fingerprints.len() as u64).to_le_bytes());
+    for &fingerprint in fingerprints {
+        fingerprint.write_le(&mut bytes);
+    }
+
+    let checksum = crc32c(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+fn decode<F: Fingerprint, R: Read>(mut reader: R, magic: [u8; 4]) -> Result<Decoded<F>, WireError> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    if header[0..4] != magic {
+        return Err(WireError::BadMagic);
+    }
+    if header[4] != FORMAT_VERSION {
+        return Err(WireError::UnsupportedVersion(header[4]));
+    }
+    if header[5] as u32 != F::BITS {
+        return Err(WireError::Corrupt(
+            "fingerprint size does not match the filter type",
+        ));
+    }
+
+    let count = usize::try_from(read_u64(&header[40..48]))
+        .map_err(|_| WireError::Corrupt("fingerprint count does not fit in memory"))?;
+    let len = usize::try_from(read_u64(&header[24..32]))
+        .map_err(|_| WireError::Corrupt("key count does not fit in memory"))?;
+
+    // read the fingerprints and checksum through `take` so a corrupt count
+    // cannot make us allocate more than the input actually holds.
+    let width = F::BITS as u64 / 8;
+    let body_len = (count as u64).saturating_mul(width).saturating_add(4);
+    let mut body = Vec::new();
+    reader.by_ref().take(body_len).read_to_end(&mut body)?;
+    if (body.len() as u64) < body_len {
+        return Err(WireError::Truncated);
+    }
+
+    let (fingerprints, checksum) = body.split_at(body.len() - 4);
+    let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    let actual = crc32c_update(crc32c(&header), fingerprints);
+    if expected != actual {
+        return Err(WireError::ChecksumMismatch { expected, actual });
+    }
+
+    Ok(Decoded {
+        hasher_seed: read_u64(&header[8..16]),
+        seed: read_u64(&header[16..24]),
+        len,
+        params: read_u64(&header[32..40]),
+        fingerprints: fingerprints
+            .chunks(width as usize)
+            .map(F::read_le)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(len: u64) -> Vec<u64> {
+        (0..len).map(|i| i * 7919).collect()
+    }
+
+    fn fp_rate(contains: impl Fn(&u64) -> bool) -> f64 {
+        (1_000_000..1_200_000).filter(|i| contains(i)).count() as f64 / 200_000.0
+    }
+
+    #[test]
+    fn xor_filter() {
+        let keys = keys(100_000);
+        let filter8: XorFilter8<u64> = XorFilter::new(&keys);
+        let filter16: XorFilter16<u64> = XorFilter::new(keys.iter());
+
+        assert_eq!(filter8.len(), 100_000);
+        assert!(keys
+            .iter()
+            .all(|key| filter8.contains(key) && filter16.contains(key)));
+        assert!(fp_rate(|i| filter8.contains(i)) < 0.006);
+        assert!(fp_rate(|i| filter16.contains(i)) < 0.0002);
+        assert!(filter8.bits_per_key() < 9.9);
+        assert!(filter16.bits_per_key() < 19.8);
+    }
+
+    #[test]
+    fn binary_fuse_filter() {
+        let keys = keys(100_000);
+        let filter8: BinaryFuseFilter8<u64> = BinaryFuseFilter::new(&keys);
+        let filter16: BinaryFuseFilter16<u64> = BinaryFuseFilter::new(keys.iter());
+
+        assert_eq!(filter8.len(), 100_000);
+        assert!(keys
+            .iter()
+            .all(|key| filter8.contains(key) && filter16.contains(key)));
+        assert!(fp_rate(|i| filter8.contains(i)) < 0.006);
+        assert!(fp_rate(|i| filter16.contains(i)) < 0.0002);
+        // the size factor shrinks towards 1.125 (9 bits per key) as the key
+        // count grows; at 100k keys it is 1.175.
+        assert!(filter8.bits_per_key() < 9.6);
+        assert!(filter16.bits_per_key() < 19.2);
+    }
+
+    #[test]
+    fn small_and_duplicate_key_sets() {
+        for len in [0, 1, 2, 3, 10, 100] {
+            let mut keys = keys(len);
+            keys.extend(keys.clone());
+
+            let xor: XorFilter8<u64> = XorFilter::new(&keys);
+            let fuse: BinaryFuseFilter8<u64> = BinaryFuseFilter::new(&keys);
+            assert_eq!(xor.len(), len as usize);
+            assert_eq!(fuse.len(), len as usize);
+            assert!(keys
+                .iter()
+                .all(|key| xor.contains(key) && fuse.contains(key)));
+        }
+
+        let words = ["apple", "banana", "cherry"];
+        let fuse: BinaryFuseFilter16<str> = BinaryFuseFilter::new(words.iter().copied());
+        assert!(fuse.contains("banana"));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let keys = keys(1000);
+
+        let xor: XorFilter16<u64> = XorFilter::new(&keys);
+        let restored = XorFilter16::<u64>::from_bytes(&xor.to_bytes()).unwrap();
+        assert_eq!(restored.len(), 1000);
+        assert!(keys.iter().all(|key| restored.contains(key)));
+        assert_eq!(restored.to_bytes(), xor.to_bytes());
+
+        let fuse: BinaryFuseFilter8<u64> = BinaryFuseFilter::new(&keys);
+        let restored = BinaryFuseFilter8::<u64>::from_bytes(&fuse.to_bytes()).unwrap();
+        assert!(keys.iter().all(|key| restored.contains(key)));
+        assert_eq!(restored.to_bytes(), fuse.to_bytes());
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        let bytes = XorFilter8::<u64>::new(&keys(100)).to_bytes();
+
+        assert!(matches!(
+            BinaryFuseFilter8::<u64>::from_bytes(&bytes),
+            Err(WireError::BadMagic)
+        ));
+        assert!(matches!(
+            XorFilter16::<u64>::from_bytes(&bytes),
+            Err(WireError::Corrupt(_))
+        ));
+        assert!(matches!(
+            XorFilter8::<u64>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(WireError::Truncated)
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LEN] ^= 1;
+        assert!(matches!(
+            XorFilter8::<u64>::from_bytes(&corrupt),
+            Err(WireError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_zero_block_length() {
+        // a well-formed, correctly checksummed encoding of a filter with no
+        // slots at all.
+        let bytes = encode::<u8>(XOR_MAGIC, 1, 2, 0, 0, &[]);
+        assert!(matches!(
+            XorFilter8::<u64>::from_bytes(&bytes),
+            Err(WireError::Corrupt("block length is zero"))
+        ));
+    }
+}